@@ -7,6 +7,10 @@ use std::{
 };
 
 use crate::error::{Ev3Error, Ev3Result};
+use crate::replay::{self, ReplayLog};
+
+// where sysfs is mounted on the brick
+const SYSFS_ROOT: &str = "/sys";
 
 #[derive(Debug)]
 pub(crate) enum FileMode {
@@ -15,8 +19,27 @@ pub(crate) enum FileMode {
     ReadWrite,
 }
 
+/// A device that lives in memory instead of in sysfs, like the ones in the `sim` module.
+pub(crate) trait VirtualDevice: Send + Sync {
+    fn read(&self, name: AttributeName) -> Ev3Result<String>;
+    fn write(&self, name: AttributeName, value: &str) -> Ev3Result<()>;
+}
+
+enum Backend {
+    File {
+        fd: Arc<Mutex<File>>,
+        // regular files keep the tail of a longer previous value, so they are truncated after each write
+        truncate: bool,
+    },
+    Virtual {
+        device: Arc<dyn VirtualDevice>,
+        name: AttributeName,
+    },
+    Replay(Arc<Mutex<ReplayLog>>),
+}
+
 pub(crate) struct Attribute {
-    fd: Arc<Mutex<File>>,
+    backend: Backend,
     path: PathBuf,
     mode: FileMode,
 }
@@ -65,6 +88,10 @@ pub(crate) enum AttributeName {
     Value6,
     Value7,
     Value8,
+
+    // led attributes
+    Brightness,
+    MaxBrightness,
 }
 
 impl Display for AttributeName {
@@ -110,6 +137,9 @@ impl Display for AttributeName {
             AttributeName::Value6 => write!(f, "value6"),
             AttributeName::Value7 => write!(f, "value7"),
             AttributeName::Value8 => write!(f, "value8"),
+
+            AttributeName::Brightness => write!(f, "brightness"),
+            AttributeName::MaxBrightness => write!(f, "max_brightness"),
         }
     }
 }
@@ -159,6 +189,9 @@ impl AttributeName {
             Value6 => Read,
             Value7 => Read,
             Value8 => Read,
+
+            Brightness => ReadWrite,
+            MaxBrightness => Read,
         }
     }
 }
@@ -167,6 +200,14 @@ impl Attribute {
     pub(crate) fn new(filename: PathBuf, mode: FileMode) -> Ev3Result<Self> {
         let path = filename.clone();
 
+        if let Some(log) = replay::active_replay() {
+            return Ok(Attribute {
+                backend: Backend::Replay(log),
+                mode,
+                path,
+            });
+        }
+
         let fd = Arc::new(Mutex::new(match mode {
             FileMode::Read => {
                 File::open(&filename).or(Err(Ev3Error::FileNotFound { path: filename }))?
@@ -181,52 +222,109 @@ impl Attribute {
                 .open(&filename)
                 .or(Err(Ev3Error::FileNotFound { path: filename }))?,
         }));
-        Ok(Attribute { fd, mode, path })
+        Ok(Attribute {
+            backend: Backend::File {
+                fd,
+                // sysfs ignores truncating, so the extra syscall is only made for fake device trees
+                truncate: !path.starts_with(SYSFS_ROOT),
+            },
+            mode,
+            path,
+        })
     }
+
+    pub(crate) fn new_virtual(
+        device: Arc<dyn VirtualDevice>,
+        filename: PathBuf,
+        name: AttributeName,
+    ) -> Self {
+        Attribute {
+            path: filename,
+            mode: name.filemode(),
+            backend: Backend::Virtual { device, name },
+        }
+    }
+
     pub(crate) fn get(&self) -> Ev3Result<String> {
         match self.mode {
-            FileMode::Read | FileMode::ReadWrite => {
-                let mut fd = self.fd.lock().expect("Tried to use a poisoned lock");
-                let mut buffer = String::new();
-                fd.seek(SeekFrom::Start(0))
-                    .map_err(|e| Ev3Error::ReadAttributeFailure {
-                        filename: self.path.clone(),
-                        os_error: e,
-                    })?;
+            FileMode::Read | FileMode::ReadWrite => match self.backend {
+                Backend::File { ref fd, .. } => {
+                    let mut fd = fd.lock().expect("Tried to use a poisoned lock");
+                    let mut buffer = String::new();
+                    fd.seek(SeekFrom::Start(0))
+                        .map_err(|e| Ev3Error::ReadAttributeFailure {
+                            filename: self.path.clone(),
+                            os_error: e,
+                        })?;
 
-                // the file is occasionally in an invalid state
-                // this usually clears up on a retry
-                for _ in 0..5 {
-                    if fd.read_to_string(&mut buffer).is_ok() {
-                        return Ok(buffer.trim().into());
+                    // the file is occasionally in an invalid state
+                    // this usually clears up on a retry
+                    for _ in 0..5 {
+                        if fd.read_to_string(&mut buffer).is_ok() {
+                            let value = buffer.trim();
+                            replay::record_read(&self.path, value);
+                            return Ok(value.into());
+                        }
                     }
-                }
 
-                // if 5 tries fail in a row, return the error
-                Err(Ev3Error::InvalidStringBytes)
-            }
+                    // if 5 tries fail in a row, return the error
+                    Err(Ev3Error::InvalidStringBytes)
+                }
+                Backend::Virtual { ref device, name } => {
+                    let value = device.read(name)?;
+                    replay::record_read(&self.path, &value);
+                    Ok(value)
+                }
+                Backend::Replay(ref log) => log
+                    .lock()
+                    .expect("Tried to use a poisoned lock")
+                    .read(&self.path)
+                    .ok_or(Ev3Error::FileNotFound {
+                        path: self.path.clone(),
+                    }),
+            },
             _ => Err(Ev3Error::PermissionDenied {
                 required_permission: FileMode::Read,
             }),
         }
     }
+
     pub(crate) fn set(&self, value: &str) -> Ev3Result<()> {
         match self.mode {
-            FileMode::Write | FileMode::ReadWrite => {
-                let mut fd = self.fd.lock().expect("Tried to use a poisoned lock");
-                fd.seek(SeekFrom::Start(0))
-                    .map_err(|e| Ev3Error::ReadAttributeFailure {
-                        filename: self.path.clone(),
-                        os_error: e,
-                    })?;
-                fd.write_all(value.as_bytes())
-                    .map_err(|e| Ev3Error::WriteAttributeFailure {
-                        filename: self.path.clone(),
-                        value: value.into(),
-                        os_error: e,
+            FileMode::Write | FileMode::ReadWrite => match self.backend {
+                Backend::File { ref fd, truncate } => {
+                    let mut fd = fd.lock().expect("Tried to use a poisoned lock");
+                    fd.seek(SeekFrom::Start(0))
+                        .map_err(|e| Ev3Error::ReadAttributeFailure {
+                            filename: self.path.clone(),
+                            os_error: e,
+                        })?;
+                    fd.write_all(value.as_bytes()).map_err(|e| {
+                        Ev3Error::WriteAttributeFailure {
+                            filename: self.path.clone(),
+                            value: value.into(),
+                            os_error: e,
+                        }
                     })?;
-                Ok(())
-            }
+
+                    if truncate {
+                        _ = fd.set_len(value.len() as u64);
+                    }
+                    replay::record_write(&self.path, value);
+                    Ok(())
+                }
+                Backend::Virtual { ref device, name } => {
+                    device.write(name, value)?;
+                    replay::record_write(&self.path, value);
+                    Ok(())
+                }
+                Backend::Replay(ref log) => {
+                    log.lock()
+                        .expect("Tried to use a poisoned lock")
+                        .write(&self.path, value);
+                    Ok(())
+                }
+            },
             _ => Err(Ev3Error::PermissionDenied {
                 required_permission: FileMode::Write,
             }),
@@ -1,9 +1,11 @@
 use crate::{
     attribute::FileMode,
-    parameters::{MotorPort, SensorPort},
+    parameters::{MotorPort, SensorPort, Side},
     sensor_driver::SensorType,
 };
+use fixed::types::I32F32;
 use std::num::ParseFloatError;
+use std::time::Duration;
 use std::{error::Error, fmt::Display, num::ParseIntError, path::PathBuf};
 
 #[derive(Debug)]
@@ -88,6 +90,49 @@ pub enum Ev3Error {
     ///
     /// This was most likely caused by a `DriveBase` not being provided a `GyroSensor`.
     NoSensorProvided,
+    /// Failed to read or write an image file.
+    ImageFailure {
+        /// The path of the image.
+        path: PathBuf,
+        /// The raw OS error.
+        os_error: std::io::Error,
+    },
+    /// An external program, like `aplay`, failed to run or exited with an error.
+    CommandFailure {
+        /// The command that was run.
+        command: String,
+        /// The raw OS error, or the exit status of the command.
+        os_error: std::io::Error,
+    },
+    /// Failed to read or write a recording file.
+    RecordingFailure {
+        /// The path of the recording.
+        path: PathBuf,
+        /// The raw OS error.
+        os_error: std::io::Error,
+    },
+    /// Failed to read or write a calibration file.
+    CalibrationFailure {
+        /// The path of the calibration file.
+        path: PathBuf,
+        /// The raw OS error.
+        os_error: std::io::Error,
+    },
+    /// A drive action stopped because of an obstacle, see `DriveBase::with_ultrasonic_guard`.
+    Blocked {
+        /// The distance to the obstacle in mm.
+        distance: I32F32,
+    },
+    /// An action took too long, see `DriveBase::with_timeout` and `RelayTuner::with_timeout`.
+    TimedOut {
+        /// How long the action was allowed to take, which is after the planned duration for drive actions.
+        timeout: Duration,
+    },
+    /// A drive action stopped because a wheel was stalled, see `DriveBase::with_stall_detection`.
+    Stalled {
+        /// The side of the stalled wheel.
+        side: Side,
+    },
 }
 
 impl Display for Ev3Error {
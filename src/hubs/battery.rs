@@ -0,0 +1,211 @@
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use tokio::time::interval;
+
+use crate::{
+    attribute::{Attribute, FileMode},
+    error::{Ev3Error, Ev3Result},
+    parameters::BatteryType,
+    tools::device_root,
+};
+
+const BATTERY_DIR: &str = "sys/class/power_supply/lego-ev3-battery";
+
+// the number of samples that the watcher averages, to ignore short sags under load
+const WATCH_SAMPLES: usize = 10;
+
+// how far above the threshold the average has to rise before the watcher can fire again
+const WATCH_HYSTERESIS_MV: u32 = 100;
+
+/// The battery of the EV3 brick.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use std::time::Duration;
+/// use ev3dev_rs::hubs::Battery;
+///
+/// let battery = Battery::new()?;
+///
+/// println!("{} mV, {} mA, {:?}", battery.voltage()?, battery.current()?, battery.battery_type()?);
+///
+/// // warns once the voltage drops below 7.2 V
+/// let _watcher = battery.watch(7200, Duration::from_secs(1), |voltage| {
+///     eprintln!("Low battery: {voltage} mV");
+/// })?;
+///
+/// // or waits for it
+/// battery.wait_for_voltage_below(7000).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Battery {
+    base_path: PathBuf,
+    voltage: Attribute,
+    current: Attribute,
+}
+
+impl Battery {
+    /// Opens the brick battery.
+    pub fn new() -> Ev3Result<Self> {
+        Self::new_with_root(device_root())
+    }
+
+    /// Opens the brick battery inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_with_root<P>(root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let base_path = root.as_ref().join(BATTERY_DIR);
+        Ok(Self {
+            voltage: Attribute::new(base_path.join("voltage_now"), FileMode::Read)?,
+            current: Attribute::new(base_path.join("current_now"), FileMode::Read)?,
+            base_path,
+        })
+    }
+
+    /// Gets the voltage of the battery in mV.
+    pub fn voltage(&self) -> Ev3Result<u32> {
+        read_millis(&self.voltage)
+    }
+
+    /// Gets the current drawn from the battery in mA.
+    pub fn current(&self) -> Ev3Result<u32> {
+        read_millis(&self.current)
+    }
+
+    /// Detects whether the brick is powered by the rechargeable battery pack or by AA batteries.
+    pub fn battery_type(&self) -> Ev3Result<BatteryType> {
+        let technology =
+            Attribute::new(self.base_path.join("technology"), FileMode::Read)?.get()?;
+
+        // the rechargeable pack is detected by the driver, AA batteries show up as "Unknown"
+        Ok(match technology.as_str() {
+            "Li-ion" => BatteryType::Rechargeable,
+            _ => BatteryType::Alkaline,
+        })
+    }
+
+    /// Waits until the battery voltage drops below the threshold in mV.
+    pub async fn wait_for_voltage_below(&self, threshold: u32) -> Ev3Result<()> {
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        while self.voltage()? >= threshold {
+            timer.tick().await;
+        }
+        Ok(())
+    }
+
+    /// Starts watching the battery voltage in a background thread,
+    /// calling `callback` with the voltage in mV once it drops below the threshold in mV.
+    ///
+    /// The voltage is read every `period` and averaged over the last 10 readings,
+    /// so short drops while the motors accelerate don't trigger the callback.
+    /// After firing, the callback won't be called again until the average rises 100 mV above the threshold.
+    ///
+    /// The watcher runs until the returned `BatteryWatcher` is dropped.
+    ///
+    /// Returns `InvalidValue` if the period is zero.
+    pub fn watch<F>(
+        &self,
+        threshold: u32,
+        period: Duration,
+        callback: F,
+    ) -> Ev3Result<BatteryWatcher>
+    where
+        F: FnMut(u32) + Send + 'static,
+    {
+        // the watcher would read the voltage as fast as it can without a period
+        if period.is_zero() {
+            return Err(Ev3Error::InvalidValue {
+                func: "Battery::watch".into(),
+                value: format!("{period:?}"),
+            });
+        }
+
+        // the attribute is opened here so that it uses the same backend as this battery
+        let voltage = Attribute::new(self.base_path.join("voltage_now"), FileMode::Read)?;
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            move || watch(voltage, threshold, period, callback, &stop)
+        });
+
+        Ok(BatteryWatcher {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+fn watch<F>(
+    voltage: Attribute,
+    threshold: u32,
+    period: Duration,
+    mut callback: F,
+    stop: &AtomicBool,
+) where
+    F: FnMut(u32),
+{
+    let mut samples = VecDeque::with_capacity(WATCH_SAMPLES);
+    let mut armed = true;
+
+    while !stop.load(Ordering::Relaxed) {
+        // readings can fail briefly, so just skip those
+        if let Ok(value) = read_millis(&voltage) {
+            if samples.len() == WATCH_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(value);
+
+            let average = samples.iter().sum::<u32>() / samples.len() as u32;
+
+            if armed && average < threshold {
+                armed = false;
+                callback(average);
+            } else if average >= threshold + WATCH_HYSTERESIS_MV {
+                armed = true;
+            }
+        }
+
+        thread::park_timeout(period);
+    }
+}
+
+// the power supply class reports micro units
+fn read_millis(attribute: &Attribute) -> Ev3Result<u32> {
+    Ok(attribute.get()?.parse::<u32>()? / 1000)
+}
+
+/// A background battery watcher, created by `Battery::watch`.
+///
+/// The watcher stops when this is dropped.
+pub struct BatteryWatcher {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for BatteryWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            _ = handle.join();
+        }
+    }
+}
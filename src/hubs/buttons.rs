@@ -0,0 +1,141 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tokio::time::interval;
+
+use crate::{
+    error::{Ev3Error, Ev3Result},
+    parameters::Button,
+    tools::device_root,
+};
+
+const BUTTONS_DEVICE: &str = "dev/input/by-path/platform-gpio_keys-event";
+
+// enough bytes to hold a bit for every key up to KEY_MAX (0x2ff)
+const KEY_BUFFER_LEN: usize = 0x2ff / 8 + 1;
+
+// _IOC(_IOC_READ, 'E', 0x18, len)
+const EVIOCGKEY: u32 = (2 << 30) | ((KEY_BUFFER_LEN as u32) << 16) | ((b'E' as u32) << 8) | 0x18;
+
+const KEYS: [(Button, usize); 6] = [
+    (Button::Up, 103),
+    (Button::Down, 108),
+    (Button::Left, 105),
+    (Button::Right, 106),
+    (Button::Center, 28),
+    (Button::Back, 14),
+];
+
+/// The buttons on the EV3 brick.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::hubs::Buttons;
+/// use ev3dev_rs::parameters::Button;
+///
+/// let buttons = Buttons::new()?;
+///
+/// println!("Pressed buttons: {:?}", buttons.pressed()?);
+///
+/// buttons.wait_for_press(Button::Center).await?;
+/// buttons.wait_for_release(Button::Center).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Buttons {
+    device: File,
+    path: PathBuf,
+}
+
+impl Buttons {
+    /// Opens the brick buttons.
+    ///
+    /// Will return `FileNotFound` if the button input device doesn't exist.
+    pub fn new() -> Ev3Result<Self> {
+        Self::new_with_root(device_root())
+    }
+
+    /// Opens the brick buttons inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_with_root<P>(root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = root.as_ref().join(BUTTONS_DEVICE);
+        let device = File::open(&path).or(Err(Ev3Error::FileNotFound { path: path.clone() }))?;
+        Ok(Self { device, path })
+    }
+
+    /// Gets a `HashSet` of the buttons that are currently pressed.
+    pub fn pressed(&self) -> Ev3Result<HashSet<Button>> {
+        let mut keys = [0u8; KEY_BUFFER_LEN];
+
+        // SAFETY: the buffer is exactly as long as the length encoded in the request
+        let result =
+            unsafe { libc::ioctl(self.device.as_raw_fd(), EVIOCGKEY as _, keys.as_mut_ptr()) };
+
+        if result < 0 {
+            return Err(Ev3Error::ReadAttributeFailure {
+                filename: self.path.clone(),
+                os_error: std::io::Error::last_os_error(),
+            });
+        }
+
+        Ok(KEYS
+            .iter()
+            .filter(|(_, code)| keys[code / 8] & (1 << (code % 8)) != 0)
+            .map(|(button, _)| *button)
+            .collect())
+    }
+
+    /// Returns `true` if the given button is currently pressed and `false` otherwise.
+    pub fn is_pressed(&self, button: Button) -> Ev3Result<bool> {
+        Ok(self.pressed()?.contains(&button))
+    }
+
+    /// Waits until the given button is pressed.
+    ///
+    /// Returns immediately if the button is already pressed.
+    pub async fn wait_for_press(&self, button: Button) -> Ev3Result<()> {
+        self.wait_for(|pressed| pressed.contains(&button)).await?;
+        Ok(())
+    }
+
+    /// Waits until the given button is released.
+    ///
+    /// Returns immediately if the button is not pressed.
+    pub async fn wait_for_release(&self, button: Button) -> Ev3Result<()> {
+        self.wait_for(|pressed| !pressed.contains(&button)).await?;
+        Ok(())
+    }
+
+    /// Waits until any button is pressed and returns the buttons that are pressed.
+    pub async fn wait_for_any_press(&self) -> Ev3Result<HashSet<Button>> {
+        self.wait_for(|pressed| !pressed.is_empty()).await
+    }
+
+    async fn wait_for<F>(&self, condition: F) -> Ev3Result<HashSet<Button>>
+    where
+        F: Fn(&HashSet<Button>) -> bool,
+    {
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        loop {
+            let pressed = self.pressed()?;
+            if condition(&pressed) {
+                return Ok(pressed);
+            }
+            timer.tick().await;
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Ev3Error, Ev3Result},
+    hubs::font::{self, CHAR_HEIGHT, CHAR_WIDTH},
+    parameters::Color,
+};
+
+// the space between characters and lines of text
+const CHAR_SPACING: i32 = 1;
+const LINE_SPACING: i32 = 1;
+
+// the largest width and height of an image, which keeps the pixels of an image well within memory
+const MAX_SIZE: u32 = 4096;
+
+/// A black and white image that can be drawn on.
+///
+/// This is what a `Screen` draws on, but it can also be used on its own,
+/// for example to prepare an image and draw it on the screen later,
+/// or to check what would be drawn in a test.
+///
+/// Coordinates start at the top left corner, and anything drawn outside the image is cut off.
+///
+/// # Examples
+/// ``` no_run
+/// # fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::hubs::Image;
+///
+/// let mut image = Image::new(178, 128);
+///
+/// image.draw_text(10, 10, "Hello!");
+/// image.draw_circle(89, 64, 20, false);
+///
+/// image.save_pbm("hello.pbm")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: i32,
+    height: i32,
+    // true is black
+    pixels: Vec<bool>,
+    cursor: i32,
+}
+
+impl Image {
+    /// Creates a new white image with the given size in pixels.
+    ///
+    /// The width and height are limited to 4096 pixels.
+    pub fn new(width: u32, height: u32) -> Self {
+        let width = width.min(MAX_SIZE);
+        let height = height.min(MAX_SIZE);
+        Self {
+            width: width as i32,
+            height: height as i32,
+            pixels: vec![false; width as usize * height as usize],
+            cursor: 0,
+        }
+    }
+
+    /// Loads an image from a PBM file.
+    ///
+    /// Both the plain (`P1`) and raw (`P4`) formats are supported.
+    pub fn load_pbm<P>(path: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| Ev3Error::ImageFailure {
+            path: path.to_path_buf(),
+            os_error: e,
+        })?;
+        Self::from_pbm(&bytes)
+    }
+
+    /// Reads an image from the contents of a PBM file.
+    ///
+    /// Both the plain (`P1`) and raw (`P4`) formats are supported.
+    ///
+    /// Returns `InvalidValue` if the contents aren't a valid PBM file,
+    /// or if the image is wider or higher than 4096 pixels.
+    pub fn from_pbm(bytes: &[u8]) -> Ev3Result<Self> {
+        let invalid = || Ev3Error::InvalidValue {
+            func: "Image::from_pbm".into(),
+            value: String::from_utf8_lossy(&bytes[..bytes.len().min(16)]).into(),
+        };
+
+        // reads the next whitespace separated token of the header, skipping comments
+        let mut pos = 0;
+        let mut token = || -> Option<&[u8]> {
+            loop {
+                while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                if pos < bytes.len() && bytes[pos] == b'#' {
+                    while pos < bytes.len() && bytes[pos] != b'\n' {
+                        pos += 1;
+                    }
+                } else {
+                    break;
+                }
+            }
+            let start = pos;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            (start < pos).then(|| &bytes[start..pos])
+        };
+
+        let magic = token().ok_or_else(invalid)?.to_vec();
+        let mut number = || -> Option<u32> { std::str::from_utf8(token()?).ok()?.parse().ok() };
+        let width = number().ok_or_else(invalid)?;
+        let height = number().ok_or_else(invalid)?;
+        if width > MAX_SIZE || height > MAX_SIZE {
+            return Err(invalid());
+        }
+
+        // the data is checked before the image is created, so a bad header can't make a huge image
+        match magic.as_slice() {
+            b"P1" => {
+                let digits: Vec<bool> = bytes[pos..]
+                    .iter()
+                    .filter(|b| **b == b'0' || **b == b'1')
+                    .take(width as usize * height as usize)
+                    .map(|b| *b == b'1')
+                    .collect();
+                if digits.len() < width as usize * height as usize {
+                    return Err(invalid());
+                }
+
+                let mut image = Image::new(width, height);
+                image.pixels = digits;
+                Ok(image)
+            }
+            b"P4" => {
+                // a single whitespace character separates the header from the data
+                let data = bytes.get(pos + 1..).ok_or_else(invalid)?;
+                let row_len = width.div_ceil(8) as usize;
+                if data.len() < row_len * height as usize {
+                    return Err(invalid());
+                }
+
+                let mut image = Image::new(width, height);
+                for y in 0..height as usize {
+                    for x in 0..width as usize {
+                        let byte = data[y * row_len + x / 8];
+                        image.pixels[y * width as usize + x] = byte & (0x80 >> (x % 8)) != 0;
+                    }
+                }
+                Ok(image)
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Saves the image as a raw (`P4`) PBM file.
+    pub fn save_pbm<P>(&self, path: P) -> Ev3Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        fs::write(path, self.to_pbm()).map_err(|e| Ev3Error::ImageFailure {
+            path: path.to_path_buf(),
+            os_error: e,
+        })
+    }
+
+    /// Gets the contents of a raw (`P4`) PBM file of the image.
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut bytes = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+        let row_len = (self.width as usize).div_ceil(8);
+
+        for y in 0..self.height {
+            let mut row = vec![0u8; row_len];
+            for x in 0..self.width {
+                if self.pixel(x, y) {
+                    row[x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+            bytes.extend(row);
+        }
+
+        bytes
+    }
+
+    /// Gets the width of the image in pixels.
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    /// Gets the height of the image in pixels.
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    /// Returns `true` if the pixel is black and `false` if it is white or outside the image.
+    pub fn pixel(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some_and(|i| self.pixels[i])
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn set(&mut self, x: i32, y: i32, black: bool) {
+        if let Some(i) = self.index(x, y) {
+            self.pixels[i] = black;
+        }
+    }
+
+    /// Clears the image to white and moves the `print` cursor back to the top.
+    pub fn clear(&mut self) {
+        self.pixels.fill(false);
+        self.cursor = 0;
+    }
+
+    /// Draws a single pixel.
+    ///
+    /// `White` and `None` draw a white pixel, while every other color draws a black pixel.
+    pub fn draw_pixel(&mut self, x: i32, y: i32, color: Color) {
+        self.set(x, y, !matches!(color, Color::White | Color::None));
+    }
+
+    /// Draws a line with the given width between two points.
+    pub fn draw_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, width: u32) {
+        let width = width.max(1) as i32;
+        let offset = (width - 1) / 2;
+
+        // Bresenham's line algorithm
+        let dx = (x2 - x1).abs();
+        let dy = -(y2 - y1).abs();
+        let step_x = if x1 < x2 { 1 } else { -1 };
+        let step_y = if y1 < y2 { 1 } else { -1 };
+        let mut error = dx + dy;
+        let (mut x, mut y) = (x1, y1);
+
+        loop {
+            for py in 0..width {
+                for px in 0..width {
+                    self.set(x + px - offset, y + py - offset, true);
+                }
+            }
+
+            if x == x2 && y == y2 {
+                break;
+            }
+
+            let double_error = 2 * error;
+            if double_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if double_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draws a box between two corners, with corners rounded by the radius `r`.
+    ///
+    /// If `fill` is `true` the box is filled in, otherwise only the outline is drawn.
+    pub fn draw_box(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, r: u32, fill: bool) {
+        let (left, right) = (x1.min(x2), x1.max(x2));
+        let (top, bottom) = (y1.min(y2), y1.max(y2));
+        let r = (r as i32).min((right - left) / 2).min((bottom - top) / 2);
+
+        let inside = |x: i32, y: i32, inset: i32| {
+            let (left, right, top, bottom) =
+                (left + inset, right - inset, top + inset, bottom - inset);
+            let r = r - inset;
+            if x < left || x > right || y < top || y > bottom {
+                return false;
+            }
+            if r <= 0 {
+                return true;
+            }
+            // distance to the center of the nearest corner circle
+            let cx = x.clamp(left + r, right - r);
+            let cy = y.clamp(top + r, bottom - r);
+            (x - cx).pow(2) + (y - cy).pow(2) <= r * r
+        };
+
+        for y in top..=bottom {
+            for x in left..=right {
+                if inside(x, y, 0) && (fill || !inside(x, y, 1)) {
+                    self.set(x, y, true);
+                }
+            }
+        }
+    }
+
+    /// Draws a circle with the given center and radius.
+    ///
+    /// If `fill` is `true` the circle is filled in, otherwise only the outline is drawn.
+    pub fn draw_circle(&mut self, x: i32, y: i32, r: u32, fill: bool) {
+        let r = r as i32;
+        // the extra r makes the edges look rounder
+        let inside = |dx: i32, dy: i32, r: i32| dx * dx + dy * dy <= r * r + r;
+
+        for dy in -r..=r {
+            for dx in -r..=r {
+                if inside(dx, dy, r) && (fill || !inside(dx, dy, r - 1)) {
+                    self.set(x + dx, y + dy, true);
+                }
+            }
+        }
+    }
+
+    /// Draws text with its top left corner at the given position.
+    ///
+    /// Each character is 5x7 pixels, and new lines start below the previous line.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str) {
+        for (row, line) in text.split('\n').enumerate() {
+            let top = y + row as i32 * (CHAR_HEIGHT + LINE_SPACING);
+            for (column, c) in line.chars().enumerate() {
+                let left = x + column as i32 * (CHAR_WIDTH + CHAR_SPACING);
+                for (dx, bits) in font::glyph(c).iter().enumerate() {
+                    for dy in 0..CHAR_HEIGHT {
+                        if bits & (1 << dy) != 0 {
+                            self.set(left + dx as i32, top + dy, true);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws another image with its top left corner at the given position.
+    ///
+    /// White pixels of the other image are drawn too, so it covers what was below it.
+    pub fn draw_image(&mut self, x: i32, y: i32, image: &Image) {
+        for iy in 0..image.height {
+            for ix in 0..image.width {
+                self.set(x + ix, y + iy, image.pixel(ix, iy));
+            }
+        }
+    }
+
+    /// Prints a line of text below the previously printed line, like a terminal.
+    ///
+    /// Once the image is full, everything is scrolled up to make room.
+    pub fn print(&mut self, text: &str) {
+        let line_height = CHAR_HEIGHT + LINE_SPACING;
+
+        for line in text.split('\n') {
+            if self.cursor + CHAR_HEIGHT > self.height {
+                self.scroll(line_height);
+                self.cursor -= line_height;
+            }
+            self.draw_text(0, self.cursor, line);
+            self.cursor += line_height;
+        }
+    }
+
+    // moves everything up by the given number of rows
+    fn scroll(&mut self, rows: i32) {
+        let rows = rows.clamp(0, self.height) as usize;
+        let width = self.width as usize;
+        self.pixels.copy_within(rows * width.., 0);
+        let len = self.pixels.len();
+        self.pixels[len - rows * width..].fill(false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbm_round_trip() -> Ev3Result<()> {
+        let mut image = Image::new(13, 5);
+        image.draw_line(0, 0, 12, 4, 1);
+
+        assert_eq!(Image::from_pbm(&image.to_pbm())?, image);
+        Ok(())
+    }
+
+    #[test]
+    fn plain_pbm() -> Ev3Result<()> {
+        let image = Image::from_pbm(b"P1\n# a comment\n3 2\n1 0 1\n0 1 0\n")?;
+
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert!(image.pixel(0, 0) && !image.pixel(1, 0) && image.pixel(2, 0));
+        assert!(!image.pixel(0, 1) && image.pixel(1, 1) && !image.pixel(2, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        // sizes that overflow or would take gigabytes of memory
+        assert!(Image::from_pbm(b"P4\n4294967295 4294967295\n").is_err());
+        assert!(Image::from_pbm(b"P1\n65536 65536\n0").is_err());
+        assert!(Image::from_pbm(b"P4\n4096 4096\n\0\0").is_err());
+
+        // too little data
+        assert!(Image::from_pbm(b"P1\n2 2\n1 0 1").is_err());
+        assert!(Image::from_pbm(b"P4\n16 2\n\0\0\0").is_err());
+        assert!(Image::from_pbm(b"P2\n1 1\n0").is_err());
+    }
+
+    #[test]
+    fn new_limits_the_size() {
+        let image = Image::new(u32::MAX, 3);
+
+        assert_eq!((image.width(), image.height()), (4096, 3));
+    }
+}
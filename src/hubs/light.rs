@@ -0,0 +1,244 @@
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use scopeguard::defer;
+use tokio::time::sleep;
+
+use crate::{
+    attribute::{Attribute, AttributeName, FileMode},
+    error::{Ev3Error, Ev3Result},
+    parameters::{Color, Side},
+    tools::device_root,
+};
+
+const LED_DIR: &str = "sys/class/leds";
+
+struct Led {
+    attribute: Attribute,
+    max_brightness: u32,
+}
+
+impl Led {
+    fn new(path: PathBuf) -> Ev3Result<Self> {
+        let max_brightness = Attribute::new(
+            path.join(AttributeName::MaxBrightness.to_string()),
+            FileMode::Read,
+        )?
+        .get()?
+        .parse()?;
+
+        let attribute = Attribute::new(
+            path.join(AttributeName::Brightness.to_string()),
+            FileMode::ReadWrite,
+        )?;
+
+        Ok(Self {
+            attribute,
+            max_brightness,
+        })
+    }
+
+    // sets the brightness as a fraction of the max brightness (0 to 1)
+    fn set(&self, fraction: f32) -> Ev3Result<()> {
+        let value = (fraction.clamp(0.0, 1.0) * self.max_brightness as f32).round() as u32;
+        self.attribute.set(&value.to_string())
+    }
+}
+
+struct SideLeds {
+    red: Led,
+    green: Led,
+}
+
+/// The status light on the EV3 brick.
+///
+/// The light is made of a red and a green LED on each side of the brick,
+/// which are mixed to get the different colors.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use std::time::Duration;
+/// use ev3dev_rs::hubs::Light;
+/// use ev3dev_rs::parameters::Color;
+///
+/// let light = Light::new()?;
+///
+/// light.on(Color::Orange)?;
+/// light.set_brightness(50)?;
+/// light.off()?;
+///
+/// // blinks until canceled, for example by select!
+/// light.blink(Color::Red, &[Duration::from_millis(500), Duration::from_millis(500)]).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Light {
+    sides: Vec<SideLeds>,
+    color: Cell<Color>,
+    brightness: Cell<u8>,
+}
+
+impl Light {
+    /// Opens the lights on both sides of the brick.
+    pub fn new() -> Ev3Result<Self> {
+        Self::new_with_root(device_root())
+    }
+
+    /// Opens the light on a single side of the brick.
+    pub fn new_side(side: Side) -> Ev3Result<Self> {
+        Self::new_side_with_root(side, device_root())
+    }
+
+    /// Opens the lights on both sides of the brick inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_with_root<P>(root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open(&[Side::Left, Side::Right], root.as_ref())
+    }
+
+    /// Opens the light on a single side of the brick inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_side_with_root<P>(side: Side, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::open(&[side], root.as_ref())
+    }
+
+    fn open(sides: &[Side], root: &Path) -> Ev3Result<Self> {
+        let dir = root.join(LED_DIR);
+
+        let sides = sides
+            .iter()
+            .map(|side| {
+                let led = match side {
+                    Side::Left => "led0",
+                    Side::Right => "led1",
+                };
+                Ok(SideLeds {
+                    red: Led::new(dir.join(format!("{led}:red:brick-status")))?,
+                    green: Led::new(dir.join(format!("{led}:green:brick-status")))?,
+                })
+            })
+            .collect::<Ev3Result<Vec<_>>>()?;
+
+        Ok(Self {
+            sides,
+            color: Cell::new(Color::None),
+            brightness: Cell::new(100),
+        })
+    }
+
+    /// Turns on the light with the given color.
+    ///
+    /// The supported colors are `Red`, `Green`, `Orange` and `Yellow`.
+    /// `None` and `Black` turn the light off.
+    pub fn on(&self, color: Color) -> Ev3Result<()> {
+        // the mix of the red and green leds that makes up each color
+        let (red, green) = match color {
+            Color::None | Color::Black => (0.0, 0.0),
+            Color::Red => (1.0, 0.0),
+            Color::Green => (0.0, 1.0),
+            Color::Orange => (1.0, 0.5),
+            Color::Yellow => (0.1, 1.0),
+            _ => {
+                return Err(Ev3Error::InvalidValue {
+                    func: "Light::on".into(),
+                    value: color.to_string(),
+                });
+            }
+        };
+
+        let brightness = self.brightness.get() as f32 / 100.0;
+
+        for side in self.sides.iter() {
+            side.red.set(red * brightness)?;
+            side.green.set(green * brightness)?;
+        }
+
+        self.color.set(color);
+        Ok(())
+    }
+
+    /// Turns off the light.
+    pub fn off(&self) -> Ev3Result<()> {
+        self.on(Color::None)
+    }
+
+    /// Sets the brightness of the light as a percentage (0 to 100).
+    ///
+    /// This also updates the light if it is currently on.
+    pub fn set_brightness(&self, brightness: u8) -> Ev3Result<()> {
+        self.brightness.set(brightness.min(100));
+        self.on(self.color.get())
+    }
+
+    /// Blinks the light with the given color until canceled.
+    ///
+    /// The durations alternate between on and off,
+    /// so `[on, off]` gives a regular blink and `[on, off, on, long_off]` gives a double blink.
+    /// If the number of durations is odd, the last one is also used for the final off step.
+    ///
+    /// The light is turned off when this is canceled.
+    ///
+    /// Returns `InvalidValue` if there are no durations, or if a duration is zero.
+    pub async fn blink(&self, color: Color, durations: &[Duration]) -> Ev3Result<()> {
+        // always end on an off step, so the pattern repeats cleanly
+        let colors: Vec<Color> = (0..durations.len().div_ceil(2) * 2)
+            .map(|i| if i % 2 == 0 { color } else { Color::None })
+            .collect();
+
+        self.animate(&colors, durations).await
+    }
+
+    /// Cycles through the given colors until canceled.
+    ///
+    /// Each color is shown for the duration with the same index,
+    /// and if there are fewer durations than colors, the last duration is repeated.
+    ///
+    /// The light is turned off when this is canceled.
+    ///
+    /// Returns `InvalidValue` if there are no colors or durations, or if a duration is zero.
+    pub async fn animate(&self, colors: &[Color], durations: &[Duration]) -> Ev3Result<()> {
+        defer! {
+            _ = self.off();
+        }
+
+        let Some(last_duration) = durations.last() else {
+            return Err(Ev3Error::InvalidValue {
+                func: "Light::animate".into(),
+                value: "no durations".into(),
+            });
+        };
+
+        if colors.is_empty() {
+            return Err(Ev3Error::InvalidValue {
+                func: "Light::animate".into(),
+                value: "no colors".into(),
+            });
+        }
+
+        // a step without any duration would never give the runtime a chance to run anything else
+        if durations.contains(&Duration::ZERO) {
+            return Err(Ev3Error::InvalidValue {
+                func: "Light::animate".into(),
+                value: "a duration of zero".into(),
+            });
+        }
+
+        loop {
+            for (i, color) in colors.iter().enumerate() {
+                self.on(*color)?;
+                sleep(*durations.get(i).unwrap_or(last_duration)).await;
+            }
+        }
+    }
+}
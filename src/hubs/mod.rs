@@ -0,0 +1,20 @@
+/// EV3 brick battery
+pub mod battery;
+/// EV3 brick buttons
+pub mod buttons;
+mod font;
+/// Images that can be drawn on
+pub mod image;
+/// EV3 brick status light
+pub mod light;
+/// EV3 screen
+pub mod screen;
+/// EV3 speaker
+pub mod speaker;
+
+pub use battery::{Battery, BatteryWatcher};
+pub use buttons::Buttons;
+pub use image::Image;
+pub use light::Light;
+pub use screen::Screen;
+pub use speaker::Speaker;
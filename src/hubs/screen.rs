@@ -0,0 +1,320 @@
+use std::{
+    cell::{Ref, RefCell},
+    fs::{File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    attribute::{Attribute, FileMode},
+    error::{Ev3Error, Ev3Result},
+    hubs::Image,
+    parameters::Color,
+    tools::device_root,
+};
+
+const FRAMEBUFFER_DEVICE: &str = "dev/fb0";
+const FRAMEBUFFER_INFO_DIR: &str = "sys/class/graphics/fb0";
+
+struct Framebuffer {
+    file: RefCell<File>,
+    path: PathBuf,
+    bits_per_pixel: u32,
+    stride: usize,
+}
+
+impl Framebuffer {
+    fn open(root: &Path) -> Ev3Result<(Self, u32, u32)> {
+        let info = root.join(FRAMEBUFFER_INFO_DIR);
+        let read = |name: &str| Attribute::new(info.join(name), FileMode::Read)?.get();
+
+        // the size is formatted as "width,height"
+        let size = read("virtual_size")?;
+        let (width, height) = size.split_once(',').ok_or(Ev3Error::InvalidValue {
+            func: "Screen::new".into(),
+            value: size.clone(),
+        })?;
+        let (width, height): (u32, u32) = (width.trim().parse()?, height.trim().parse()?);
+
+        let bits_per_pixel: u32 = read("bits_per_pixel")?.parse()?;
+        if ![1, 16, 32].contains(&bits_per_pixel) {
+            return Err(Ev3Error::InvalidValue {
+                func: "Screen::new".into(),
+                value: format!("{bits_per_pixel} bits per pixel"),
+            });
+        }
+
+        // every row has to fit in the stride, or drawing would write past the end of the row
+        let stride: usize = read("stride")?.parse()?;
+        let row_len = (width as usize * bits_per_pixel as usize).div_ceil(8);
+        if stride < row_len {
+            return Err(Ev3Error::InvalidValue {
+                func: "Screen::new".into(),
+                value: format!("a stride of {stride} bytes for {width} pixels"),
+            });
+        }
+
+        let path = root.join(FRAMEBUFFER_DEVICE);
+        let file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .or(Err(Ev3Error::FileNotFound { path: path.clone() }))?;
+
+        Ok((
+            Self {
+                file: RefCell::new(file),
+                path,
+                bits_per_pixel,
+                stride,
+            },
+            width,
+            height,
+        ))
+    }
+
+    fn write(&self, image: &Image) -> Ev3Result<()> {
+        let mut frame = vec![0u8; self.stride * image.height() as usize];
+
+        for y in 0..image.height() as usize {
+            let row = &mut frame[y * self.stride..(y + 1) * self.stride];
+            for x in 0..image.width() as usize {
+                let black = image.pixel(x as i32, y as i32);
+                match self.bits_per_pixel {
+                    // 1 is black and the lowest bit is the leftmost pixel
+                    1 => {
+                        if black {
+                            row[x / 8] |= 1 << (x % 8);
+                        }
+                    }
+                    // RGB565
+                    16 => {
+                        let value: u16 = if black { 0 } else { 0xffff };
+                        row[x * 2..x * 2 + 2].copy_from_slice(&value.to_le_bytes());
+                    }
+                    // XRGB8888
+                    32 => {
+                        let value: u32 = if black { 0 } else { 0x00ff_ffff };
+                        row[x * 4..x * 4 + 4].copy_from_slice(&value.to_le_bytes());
+                    }
+                    _ => {
+                        return Err(Ev3Error::InvalidValue {
+                            func: "Screen::update".into(),
+                            value: format!("{} bits per pixel", self.bits_per_pixel),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.write_all(&frame))
+            .map_err(|e| Ev3Error::WriteAttributeFailure {
+                filename: self.path.clone(),
+                value: "frame".into(),
+                os_error: e,
+            })
+    }
+}
+
+/// The EV3 screen.
+///
+/// Everything is drawn on an off-screen `Image` first,
+/// which is then copied to the screen after every drawing command.
+///
+/// A screen can also be created without a display with `Screen::off_screen`,
+/// which is useful for tests. See `image` and `save_pbm`.
+///
+/// # Examples
+/// ``` no_run
+/// # fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::hubs::Screen;
+///
+/// let screen = Screen::new()?;
+///
+/// screen.clear()?;
+/// screen.print("Hello!")?;
+/// screen.draw_box(10, 20, 60, 50, 3, true)?;
+/// screen.draw_circle(120, 40, 15, false)?;
+/// screen.draw_line(0, 127, 177, 127, 2)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Screen {
+    image: RefCell<Image>,
+    framebuffer: Option<Framebuffer>,
+}
+
+impl Screen {
+    /// Opens the EV3 screen.
+    pub fn new() -> Ev3Result<Self> {
+        Self::new_with_root(device_root())
+    }
+
+    /// Opens the EV3 screen inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_with_root<P>(root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let (framebuffer, width, height) = Framebuffer::open(root.as_ref())?;
+        let screen = Self {
+            image: RefCell::new(Image::new(width, height)),
+            framebuffer: Some(framebuffer),
+        };
+        screen.update()?;
+        Ok(screen)
+    }
+
+    /// Creates a screen that only draws to an off-screen image of the given size.
+    ///
+    /// The EV3 screen is 178x128 pixels.
+    pub fn off_screen(width: u32, height: u32) -> Self {
+        Self {
+            image: RefCell::new(Image::new(width, height)),
+            framebuffer: None,
+        }
+    }
+
+    /// Gets the width of the screen in pixels.
+    pub fn width(&self) -> u32 {
+        self.image.borrow().width()
+    }
+
+    /// Gets the height of the screen in pixels.
+    pub fn height(&self) -> u32 {
+        self.image.borrow().height()
+    }
+
+    /// Gets the image that is currently shown on the screen.
+    pub fn image(&self) -> Ref<'_, Image> {
+        self.image.borrow()
+    }
+
+    /// Saves what is currently shown on the screen as a PBM file.
+    pub fn save_pbm<P>(&self, path: P) -> Ev3Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        self.image.borrow().save_pbm(path)
+    }
+
+    /// Copies the off-screen image to the screen.
+    ///
+    /// This is done automatically after every drawing command.
+    pub fn update(&self) -> Ev3Result<()> {
+        match self.framebuffer {
+            Some(ref framebuffer) => framebuffer.write(&self.image.borrow()),
+            None => Ok(()),
+        }
+    }
+
+    fn draw<F>(&self, f: F) -> Ev3Result<()>
+    where
+        F: FnOnce(&mut Image),
+    {
+        f(&mut self.image.borrow_mut());
+        self.update()
+    }
+
+    /// Clears the screen to white.
+    pub fn clear(&self) -> Ev3Result<()> {
+        self.draw(|image| image.clear())
+    }
+
+    /// Draws a single pixel, see `Image::draw_pixel`.
+    pub fn draw_pixel(&self, x: i32, y: i32, color: Color) -> Ev3Result<()> {
+        self.draw(|image| image.draw_pixel(x, y, color))
+    }
+
+    /// Draws a line with the given width between two points.
+    pub fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, width: u32) -> Ev3Result<()> {
+        self.draw(|image| image.draw_line(x1, y1, x2, y2, width))
+    }
+
+    /// Draws a box between two corners, with corners rounded by the radius `r`.
+    pub fn draw_box(
+        &self,
+        x1: i32,
+        y1: i32,
+        x2: i32,
+        y2: i32,
+        r: u32,
+        fill: bool,
+    ) -> Ev3Result<()> {
+        self.draw(|image| image.draw_box(x1, y1, x2, y2, r, fill))
+    }
+
+    /// Draws a circle with the given center and radius.
+    pub fn draw_circle(&self, x: i32, y: i32, r: u32, fill: bool) -> Ev3Result<()> {
+        self.draw(|image| image.draw_circle(x, y, r, fill))
+    }
+
+    /// Draws text with its top left corner at the given position.
+    pub fn draw_text(&self, x: i32, y: i32, text: &str) -> Ev3Result<()> {
+        self.draw(|image| image.draw_text(x, y, text))
+    }
+
+    /// Draws an image with its top left corner at the given position.
+    pub fn draw_image(&self, x: i32, y: i32, other: &Image) -> Ev3Result<()> {
+        self.draw(|image| image.draw_image(x, y, other))
+    }
+
+    /// Prints a line of text below the previously printed line, scrolling once the screen is full.
+    pub fn print(&self, text: &str) -> Ev3Result<()> {
+        self.draw(|image| image.print(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    // creates a fake framebuffer with the given info, and returns its root
+    fn fake_framebuffer(name: &str, bits_per_pixel: &str, stride: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("ev3dev-rs-{name}-{}", std::process::id()));
+        let info = root.join(FRAMEBUFFER_INFO_DIR);
+        fs::create_dir_all(&info).expect("failed to create the fake framebuffer info");
+        fs::create_dir_all(root.join("dev")).expect("failed to create the fake dev directory");
+
+        for (name, value) in [
+            ("virtual_size", "178,128"),
+            ("bits_per_pixel", bits_per_pixel),
+            ("stride", stride),
+        ] {
+            fs::write(info.join(name), value).expect("failed to create a fake attribute");
+        }
+        fs::write(root.join(FRAMEBUFFER_DEVICE), "")
+            .expect("failed to create the fake framebuffer");
+        root
+    }
+
+    #[test]
+    fn draws_to_the_framebuffer() -> Ev3Result<()> {
+        let root = fake_framebuffer("screen", "1", "24");
+
+        let screen = Screen::new_with_root(&root)?;
+        screen.draw_pixel(9, 1, Color::Black)?;
+
+        let frame = fs::read(root.join(FRAMEBUFFER_DEVICE)).expect("failed to read the frame");
+        _ = fs::remove_dir_all(&root);
+
+        assert_eq!(frame.len(), 24 * 128);
+        assert_eq!(frame[24 + 1], 0b10);
+        assert_eq!(frame.iter().filter(|byte| **byte != 0).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn a_short_stride_is_rejected() {
+        let root = fake_framebuffer("short-stride", "16", "200");
+
+        let screen = Screen::new_with_root(&root);
+        _ = fs::remove_dir_all(&root);
+
+        assert!(matches!(screen, Err(Ev3Error::InvalidValue { .. })));
+    }
+}
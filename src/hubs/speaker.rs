@@ -0,0 +1,323 @@
+use std::{
+    cell::Cell,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use scopeguard::{defer, guard};
+use tokio::time::{interval, sleep};
+
+use crate::{
+    error::{Ev3Error, Ev3Result},
+    tools::device_root,
+};
+
+const SOUND_DEVICE: &str = "dev/input/by-path/platform-sound-event";
+
+// from linux/input-event-codes.h
+const EV_SND: u16 = 0x12;
+const SND_TONE: u16 = 0x02;
+
+/// A single parsed note, see `Speaker::play_notes`.
+struct Note {
+    // None is a rest
+    frequency: Option<f32>,
+    // as a fraction of a whole note
+    length: f32,
+    tied: bool,
+}
+
+impl Note {
+    fn parse(note: &str) -> Option<Self> {
+        let (pitch, length) = note.split_once('/')?;
+
+        let (length, tied) = match length.strip_suffix('_') {
+            Some(length) => (length, true),
+            None => (length, false),
+        };
+        let (length, dotted) = match length.strip_suffix('.') {
+            Some(length) => (length, true),
+            None => (length, false),
+        };
+        let length = 1.0 / length.parse::<u8>().ok().filter(|l| *l > 0)? as f32;
+        let length = if dotted { length * 1.5 } else { length };
+
+        if pitch == "R" {
+            return Some(Self {
+                frequency: None,
+                length,
+                tied,
+            });
+        }
+
+        let mut chars = pitch.chars();
+        let semitone: i32 = match chars.next()? {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let rest = chars.as_str();
+        let (semitone, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (semitone + 1, octave)
+        } else if let Some(octave) = rest.strip_prefix('b') {
+            (semitone - 1, octave)
+        } else {
+            (semitone, rest)
+        };
+        let octave: i32 = octave.parse().ok().filter(|o| (2..=8).contains(o))?;
+
+        // MIDI note number, where A4 (440 Hz) is 69
+        let midi = 12 * (octave + 1) + semitone;
+
+        Some(Self {
+            frequency: Some(440.0 * 2f32.powf((midi - 69) as f32 / 12.0)),
+            length,
+            tied,
+        })
+    }
+}
+
+/// The speaker on the EV3 brick.
+///
+/// Tones are played through the sound input device, and sound files are played with ALSA's `aplay`.
+///
+/// All sounds are async and stop when canceled, so they can be raced with other actions with `select!`.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use std::time::Duration;
+/// use ev3dev_rs::hubs::Speaker;
+///
+/// let speaker = Speaker::new()?;
+///
+/// speaker.set_volume(50)?;
+/// speaker.beep(500, Duration::from_millis(100)).await?;
+/// speaker.play_notes(&["C4/4", "E4/4", "G4/4", "R/8", "C5/2."], 120).await?;
+/// speaker.play_file("/home/robot/fanfare.wav").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Speaker {
+    device: File,
+    path: PathBuf,
+    volume: Cell<u8>,
+}
+
+impl Speaker {
+    /// Opens the brick speaker.
+    ///
+    /// Will return `FileNotFound` if the sound input device doesn't exist.
+    pub fn new() -> Ev3Result<Self> {
+        Self::new_with_root(device_root())
+    }
+
+    /// Opens the brick speaker inside of a custom device root.
+    ///
+    /// See `tools::set_device_root`.
+    pub fn new_with_root<P>(root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = root.as_ref().join(SOUND_DEVICE);
+        let device = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .or(Err(Ev3Error::FileNotFound { path: path.clone() }))?;
+
+        Ok(Self {
+            device,
+            path,
+            volume: Cell::new(100),
+        })
+    }
+
+    /// Sets the volume of both tones and sound files as a percentage (0 to 100).
+    ///
+    /// This uses ALSA's `amixer`.
+    pub fn set_volume(&self, volume: u8) -> Ev3Result<()> {
+        let volume = volume.min(100);
+        for control in ["Beep", "PCM"] {
+            let mut command = Command::new("amixer");
+            command.args(["-q", "set", control, &format!("{volume}%")]);
+            run(&mut command)?;
+        }
+        self.volume.set(volume);
+        Ok(())
+    }
+
+    /// Gets the volume that was last set with `set_volume`.
+    ///
+    /// This is 100 if the volume was never set.
+    pub fn volume(&self) -> u8 {
+        self.volume.get()
+    }
+
+    /// Plays a tone with the given frequency in Hz for the given duration.
+    pub async fn beep(&self, frequency: u32, duration: Duration) -> Ev3Result<()> {
+        defer! {
+            _ = self.tone(0);
+        }
+
+        self.tone(frequency)?;
+        sleep(duration).await;
+        Ok(())
+    }
+
+    /// Plays a sequence of notes at the given tempo in beats (quarter notes) per minute.
+    ///
+    /// Notes use the same format as pybricks:
+    /// the note name (`C` through `B`, optionally followed by `#` or `b`),
+    /// the octave (2 to 8), a `/`, and the fraction of a whole note.
+    /// A `.` after the fraction makes it a dotted note and a `_` ties it to the next note.
+    /// `R` is a rest.
+    ///
+    /// For example, `"C#5/8."` is a dotted eighth C sharp and `"R/4"` is a quarter rest.
+    ///
+    /// All notes are checked before anything is played.
+    pub async fn play_notes(&self, notes: &[&str], tempo: u32) -> Ev3Result<()> {
+        let parsed = notes
+            .iter()
+            .map(|note| {
+                Note::parse(note).ok_or(Ev3Error::InvalidValue {
+                    func: "Speaker::play_notes".into(),
+                    value: note.to_string(),
+                })
+            })
+            .collect::<Ev3Result<Vec<_>>>()?;
+
+        if tempo == 0 {
+            return Err(Ev3Error::InvalidValue {
+                func: "Speaker::play_notes".into(),
+                value: tempo.to_string(),
+            });
+        }
+
+        defer! {
+            _ = self.tone(0);
+        }
+
+        // a whole note is 4 beats
+        let whole_note = 4.0 * 60.0 / tempo as f32;
+
+        for note in parsed {
+            let duration = Duration::from_secs_f32(whole_note * note.length);
+            match note.frequency {
+                Some(frequency) if !note.tied => {
+                    // leave a short gap so repeated notes don't run together
+                    self.tone(frequency.round() as u32)?;
+                    sleep(duration.mul_f32(7.0 / 8.0)).await;
+                    self.tone(0)?;
+                    sleep(duration.mul_f32(1.0 / 8.0)).await;
+                }
+                Some(frequency) => {
+                    self.tone(frequency.round() as u32)?;
+                    sleep(duration).await;
+                }
+                None => {
+                    self.tone(0)?;
+                    sleep(duration).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Plays a sound file, like a WAV file, with ALSA's `aplay`.
+    ///
+    /// Playback is stopped if this is canceled.
+    pub async fn play_file<P>(&self, path: P) -> Ev3Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let mut command = Command::new("aplay");
+        command.arg("-q").arg(path.as_ref());
+        // stops playback if this is canceled
+        let mut child = guard(spawn(&mut command)?, |mut child| {
+            _ = child.kill();
+            _ = child.wait();
+        });
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        loop {
+            timer.tick().await;
+            let status = child.try_wait().map_err(|e| Ev3Error::CommandFailure {
+                command: format!("{command:?}"),
+                os_error: e,
+            })?;
+            if let Some(status) = status {
+                return check_status(&command, status);
+            }
+        }
+    }
+
+    // plays a tone until another one is played, where 0 stops the tone
+    fn tone(&self, frequency: u32) -> Ev3Result<()> {
+        // SAFETY: input_event is plain old data, so all zeros is a valid value
+        let mut event: libc::input_event = unsafe { std::mem::zeroed() };
+        event.type_ = EV_SND;
+        event.code = SND_TONE;
+        event.value = frequency as i32;
+
+        // SAFETY: the slice covers exactly the bytes of the event, which lives until the write is done
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                (&raw const event).cast::<u8>(),
+                size_of::<libc::input_event>(),
+            )
+        };
+
+        (&self.device)
+            .write_all(bytes)
+            .map_err(|e| Ev3Error::WriteAttributeFailure {
+                filename: self.path.clone(),
+                value: frequency.to_string(),
+                os_error: e,
+            })
+    }
+}
+
+fn spawn(command: &mut Command) -> Ev3Result<Child> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| Ev3Error::CommandFailure {
+            command: format!("{command:?}"),
+            os_error: e,
+        })
+}
+
+fn run(command: &mut Command) -> Ev3Result<()> {
+    let status = spawn(command)?
+        .wait()
+        .map_err(|e| Ev3Error::CommandFailure {
+            command: format!("{command:?}"),
+            os_error: e,
+        })?;
+    check_status(command, status)
+}
+
+fn check_status(command: &Command, status: std::process::ExitStatus) -> Ev3Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Ev3Error::CommandFailure {
+            command: format!("{command:?}"),
+            os_error: std::io::Error::other(status.to_string()),
+        })
+    }
+}
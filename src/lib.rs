@@ -116,15 +116,22 @@
 mod attribute;
 mod enum_string;
 mod error;
+/// Functions built into the EV3 brick.
+pub mod hubs;
 mod motor_driver;
 /// Parameters used in the ev3dev_rs crate.
 pub mod parameters;
-mod pid;
 /// Devices that can connect to the robot.
 pub mod pupdevices;
+/// Recording and replaying of device traffic.
+pub mod replay;
 /// Higher level abstractions.
 pub mod robotics;
+/// Stopping all motors when the program ends unexpectedly.
+pub mod safety;
 mod sensor_driver;
+/// Simulated devices for running without an EV3.
+pub mod sim;
 /// Additional tools.
 pub mod tools;
 
@@ -1,30 +1,47 @@
-use crate::attribute::{Attribute, AttributeName, FileMode};
+use crate::attribute::{Attribute, AttributeName, FileMode, VirtualDevice};
 use crate::enum_string::AsStr;
 use crate::error::{Ev3Error, Ev3Result};
 use crate::parameters::MotorPort;
+use crate::replay;
+use crate::sim;
+use crate::tools::device_root;
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
-use std::{collections::HashMap, fs};
+use std::collections::HashMap;
 
-static MOTOR_DIR: &str = "/sys/class/tacho-motor";
+static MOTOR_DIR: &str = "sys/class/tacho-motor";
+
+// simulated devices have no files, but their attributes are recorded under this directory
+static SIM_DIR: &str = "sim";
 
 pub(crate) struct MotorDriver {
     base_path: PathBuf,
+    device: Option<Arc<dyn VirtualDevice>>,
     attributes: RefCell<HashMap<AttributeName, Attribute>>,
 }
 
 impl MotorDriver {
     pub(crate) fn new(port: MotorPort) -> Ev3Result<Self> {
-        if let Ok(entries) = fs::read_dir(MOTOR_DIR) {
-            for entry in entries {
-                if let Ok(direntry) = entry.map(|e| e.path().to_path_buf())
-                    && let Ok(address) = MotorPort::from_str(
-                        fs::read_to_string(direntry.join("address"))
-                            .or(Err(Ev3Error::InvalidPath))?
-                            .trim(),
-                    )
+        // simulated motors take priority over the device tree
+        if let Some(device) = sim::find_motor(port) {
+            return Ok(Self {
+                base_path: Path::new(SIM_DIR).join(MOTOR_DIR).join(port.as_str()),
+                device: Some(device),
+                attributes: RefCell::new(HashMap::new()),
+            });
+        }
+
+        Self::new_with_root(port, &device_root())
+    }
+
+    pub(crate) fn new_with_root(port: MotorPort, root: &Path) -> Ev3Result<Self> {
+        if let Some(entries) = replay::read_dir(&root.join(MOTOR_DIR)) {
+            for direntry in entries {
+                if let Ok(address) =
+                    MotorPort::from_str(&replay::read_to_string(&direntry.join("address"))?)
                     && address == port
                 {
                     let mut attributes = HashMap::new();
@@ -36,6 +53,7 @@ impl MotorDriver {
 
                     return Ok(Self {
                         base_path: direntry,
+                        device: None,
                         attributes: RefCell::new(attributes),
                     });
                 }
@@ -45,13 +63,24 @@ impl MotorDriver {
         Err(Ev3Error::MotorNotFound { port })
     }
 
+    pub(crate) fn open_attribute(&self, name: AttributeName) -> Ev3Result<Attribute> {
+        match self.device {
+            Some(ref device) => Ok(Attribute::new_virtual(
+                device.clone(),
+                self.base_path.join(name.to_string()),
+                name,
+            )),
+            None => Attribute::new(self.base_path.join(name.to_string()), name.filemode()),
+        }
+    }
+
     pub(crate) fn read_attribute(&self, name: AttributeName) -> Ev3Result<String> {
         if let Some(attr) = self.attributes.borrow().get(&name) {
             attr.get()
         } else {
             // if the value is not in the hashmap, create a new attribute,
             // get its current value, and insert it into the hashmap
-            let attr = Attribute::new(self.base_path.join(name.to_string()), name.filemode())?;
+            let attr = self.open_attribute(name)?;
             let val = attr.get()?;
             _ = self.attributes.borrow_mut().insert(name, attr);
             Ok(val)
@@ -67,7 +96,7 @@ impl MotorDriver {
         } else {
             // if the value is not in the hashmap, create a new attribute,
             // set its value, and insert it into the hashmap
-            let attr = Attribute::new(self.base_path.join(name.to_string()), name.filemode())?;
+            let attr = self.open_attribute(name)?;
             attr.set(value.as_str())?;
             _ = self.attributes.borrow_mut().insert(name, attr);
             Ok(())
@@ -83,7 +112,7 @@ impl MotorDriver {
         } else {
             // if the value is not in the hashmap, create a new attribute,
             // set its value, and insert it into the hashmap
-            let attr = Attribute::new(self.base_path.join(name.to_string()), name.filemode())?;
+            let attr = self.open_attribute(name)?;
             attr.set(&value.to_string())?;
             _ = self.attributes.borrow_mut().insert(name, attr);
             Ok(())
@@ -38,8 +38,9 @@ pub_enum_str! {
     (Hold, "hold"),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 #[allow(missing_docs)]
+#[non_exhaustive]
 pub enum Color {
     None,
     Black,
@@ -49,6 +50,7 @@ pub enum Color {
     Red,
     White,
     Brown,
+    Orange,
 }
 
 impl Display for Color {
@@ -65,6 +67,7 @@ impl Display for Color {
                 Self::Red => "Red",
                 Self::White => "White",
                 Self::Brown => "Brown",
+                Self::Orange => "Orange",
             }
         )
     }
@@ -90,13 +93,41 @@ impl FromStr for Color {
     }
 }
 
-/// A list of buttons on the EV3 beacon remote.
-#[derive(PartialEq, Eq, Hash, Debug)]
+/// A side of the robot or the brick.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 #[allow(missing_docs)]
+#[non_exhaustive]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A list of buttons on the EV3 beacon remote and the EV3 brick.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[allow(missing_docs)]
+#[non_exhaustive]
 pub enum Button {
+    // beacon remote buttons
     RedUp,
     BlueUp,
     RedDown,
     BlueDown,
     BeaconOn,
+
+    // brick buttons
+    Up,
+    Down,
+    Left,
+    Right,
+    Center,
+    Back,
+}
+
+/// The kind of battery that powers the brick.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum BatteryType {
+    /// The EV3 rechargeable battery pack.
+    Rechargeable,
+    /// Six AA batteries.
+    Alkaline,
 }
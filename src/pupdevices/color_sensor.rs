@@ -4,6 +4,7 @@ use crate::{
     parameters::{Color, SensorPort},
     sensor_driver::{SensorDriver, SensorMode::*, SensorType},
 };
+use std::path::Path;
 use std::str::FromStr;
 
 /// Stock EV3 Color Sensor
@@ -38,6 +39,17 @@ impl ColorSensor {
         Ok(Self { driver })
     }
 
+    /// Find a `ColorSensor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: SensorPort, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let driver = SensorDriver::new_with_root(SensorType::Color, port, root.as_ref())?;
+        Ok(Self { driver })
+    }
+
     /// Get the reflected light intensity of the sensor as a percentage (0 to 100).
     pub fn reflection(&self) -> Ev3Result<u8> {
         if self.driver.mode.get() != ColorReflectedLight {
@@ -4,6 +4,7 @@ use crate::{
     parameters::SensorPort,
     sensor_driver::{SensorDriver, SensorMode::*, SensorType},
 };
+use std::path::Path;
 
 /// Stock EV3 Gyro Sensor
 ///
@@ -34,6 +35,17 @@ impl GyroSensor {
         Ok(Self { driver })
     }
 
+    /// Find a `GyroSensor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: SensorPort, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let driver = SensorDriver::new_with_root(SensorType::Gyro, port, root.as_ref())?;
+        Ok(Self { driver })
+    }
+
     /// Get the current heading of the sensor in degrees (-32768 to 32767).
     pub fn heading(&self) -> Ev3Result<i16> {
         match self.driver.mode.get() {
@@ -1,4 +1,5 @@
 use std::collections::HashSet;
+use std::path::Path;
 
 use crate::{
     attribute::AttributeName,
@@ -27,6 +28,17 @@ impl InfraredSensor {
         Ok(Self { driver })
     }
 
+    /// Find an `InfraredSensor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: SensorPort, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let driver = SensorDriver::new_with_root(SensorType::Infrared, port, root.as_ref())?;
+        Ok(Self { driver })
+    }
+
     /// Get the proximity value of the sensor as a percentage (0 to 100).
     ///
     /// 100% is approximately 70cm/27in.
@@ -42,6 +54,14 @@ impl InfraredSensor {
         Ok(self.driver.read_attribute(AttributeName::Value0)?.parse()?)
     }
 
+    // A rough distance in mm from the proximity, or None if the sensor doesn't see anything
+    pub(crate) fn distance_mm(&self) -> Ev3Result<Option<f64>> {
+        let proximity = self.proximity()?;
+
+        // 100% is approximately 70cm
+        Ok((proximity < 100).then_some(proximity as f64 * 7.0))
+    }
+
     #[inline]
     /// Get a `HashSet` of buttons currently pressed on the remote control channel 1.
     ///
@@ -9,8 +9,9 @@ use crate::{
     error::{Ev3Error, Ev3Result},
     motor_driver::MotorDriver,
     parameters::{Direction, MotorPort, Stop},
+    safety::{self, Registration},
 };
-use std::{cell::Cell, collections::HashSet, str::FromStr, time::Duration};
+use std::{cell::Cell, collections::HashSet, path::Path, str::FromStr, time::Duration};
 
 enum_str! {
     Command,
@@ -43,6 +44,8 @@ pub struct Motor {
     count_per_rot: u32,
     count_per_degree: u32,
     pub(crate) max_speed: I32F32,
+    // stops the motor when it is dropped, see the `safety` module
+    _registration: Registration,
 }
 
 impl Motor {
@@ -52,6 +55,9 @@ impl Motor {
     ///
     /// Note that the motor is not reset upon initialization.
     ///
+    /// The motor is stopped when it is dropped, or when the program panics or is interrupted.
+    /// See the `safety` module.
+    ///
     /// # Examples
     ///
     /// ``` no_run
@@ -63,8 +69,20 @@ impl Motor {
     /// motor.run_target(300, 360)?;
     /// ```
     pub fn new(port: MotorPort, direction: Direction) -> Ev3Result<Self> {
-        let driver = MotorDriver::new(port)?;
+        Self::from_driver(MotorDriver::new(port)?, direction)
+    }
+
+    /// Tries to find a `Motor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: MotorPort, direction: Direction, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_driver(MotorDriver::new_with_root(port, root.as_ref())?, direction)
+    }
 
+    fn from_driver(driver: MotorDriver, direction: Direction) -> Ev3Result<Self> {
         // reset the motor upon initialization
         driver.set_attribute_enum(AttributeName::Command, Command::Reset)?;
 
@@ -74,6 +92,8 @@ impl Motor {
             .read_attribute(AttributeName::CountPerRotation)?
             .parse()?;
 
+        let registration = safety::register(&driver)?;
+
         Ok(Self {
             driver,
             direction,
@@ -81,6 +101,7 @@ impl Motor {
             count_per_rot,
             count_per_degree: count_per_rot / 360,
             max_speed: I32F32::from_num(1000),
+            _registration: registration,
         })
     }
 
@@ -113,6 +134,11 @@ impl Motor {
             .set_attribute_enum(AttributeName::StopAction, action)
     }
 
+    /// Gets the stop action for the `Motor`
+    pub fn stop_action(&self) -> Ev3Result<Stop> {
+        Stop::from_str(&self.driver.read_attribute(AttributeName::StopAction)?)
+    }
+
     async fn wait_for_stop(&self) -> Ev3Result<()> {
         defer! {
             _ = self.send_command(Command::Stop);
@@ -201,6 +227,25 @@ impl Motor {
             / self.count_per_degree as i32)
     }
 
+    /// Gets the speed of the motor in degrees per second.
+    pub fn speed(&self) -> Ev3Result<i32> {
+        Ok(self
+            .driver
+            .read_attribute(AttributeName::Speed)?
+            .parse::<i32>()?
+            / self.count_per_degree as i32)
+    }
+
+    /// Gets the duty cycle of the motor as a percentage (-100 to 100).
+    ///
+    /// This is how hard the motor is working, so a high duty cycle at a low speed means that the motor is struggling.
+    pub fn duty_cycle(&self) -> Ev3Result<i32> {
+        Ok(self
+            .driver
+            .read_attribute(AttributeName::DutyCycle)?
+            .parse::<i32>()?)
+    }
+
     /// Runs the motor at a constant speed by a given angle.
     pub async fn run_angle(&self, speed: i32, rotation_angle: i32) -> Ev3Result<()> {
         self.set_speed(speed)?;
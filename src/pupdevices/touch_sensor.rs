@@ -4,6 +4,7 @@ use crate::{
     parameters::SensorPort,
     sensor_driver::{SensorDriver, SensorType},
 };
+use std::path::Path;
 
 /// A stock EV3 touch sensor.
 ///
@@ -31,6 +32,17 @@ impl TouchSensor {
         Ok(Self { driver })
     }
 
+    /// Find a `TouchSensor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: SensorPort, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let driver = SensorDriver::new_with_root(SensorType::Touch, port, root.as_ref())?;
+        Ok(Self { driver })
+    }
+
     /// Returns `true` if the sensor is currently pressed and `false` otherwise.
     pub fn pressed(&self) -> Ev3Result<bool> {
         // only one possible mode, no need to check
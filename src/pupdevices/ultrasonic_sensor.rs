@@ -4,6 +4,7 @@ use crate::{
     parameters::SensorPort,
     sensor_driver::{SensorDriver, SensorMode, SensorType},
 };
+use std::path::Path;
 
 /// A stock EV3 ultrasonic sensor.
 ///
@@ -32,6 +33,17 @@ impl UltrasonicSensor {
         Ok(Self { driver })
     }
 
+    /// Find an `UltrasonicSensor` on the given port inside of a custom device root.
+    ///
+    /// This is useful for running against a fake device tree, see `tools::set_device_root`.
+    pub fn new_with_root<P>(port: SensorPort, root: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let driver = SensorDriver::new_with_root(SensorType::Ultrasonic, port, root.as_ref())?;
+        Ok(Self { driver })
+    }
+
     /// Get the distance value of the sensor in inches to one decimal place (0-2550).
     pub fn distance_in(&self) -> Ev3Result<f32> {
         if self.driver.mode.get() != SensorMode::UltrasonicDistanceIn {
@@ -55,4 +67,12 @@ impl UltrasonicSensor {
             .parse::<f32>()?
             / 10.0)
     }
+
+    // The distance in mm, or None if the sensor didn't hear an echo
+    pub(crate) fn distance_mm(&self) -> Ev3Result<Option<f64>> {
+        let distance_cm = self.distance_cm()?;
+
+        // the sensor reports 255 cm when it doesn't hear an echo
+        Ok((distance_cm < 255.0).then_some(distance_cm as f64 * 10.0))
+    }
 }
@@ -0,0 +1,507 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::error::{Ev3Error, Ev3Result};
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static RECORDER: Mutex<Option<RecorderState>> = Mutex::new(None);
+// tells the recorders apart, so that an old recorder can't stop a newer recording
+static NEXT_RECORDER_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static REPLAY: RefCell<Option<Arc<Mutex<ReplayLog>>>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Entry {
+    Read,
+    Write,
+    List,
+}
+
+impl Entry {
+    fn as_str(&self) -> &str {
+        match self {
+            Entry::Read => "R",
+            Entry::Write => "W",
+            Entry::List => "L",
+        }
+    }
+}
+
+struct RecorderState {
+    id: u64,
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn record(entry: Entry, path: &Path, value: &str) {
+    if !RECORDING.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Some(ref mut state) = *RECORDER.lock().expect("Tried to use a poisoned lock") {
+        // a failed write shouldn't stop the robot, so the error is ignored
+        _ = writeln!(
+            state.writer,
+            "{}\t{}\t{}\t{}",
+            state.start.elapsed().as_micros(),
+            entry.as_str(),
+            escape(&path.to_string_lossy()),
+            escape(value)
+        );
+    }
+}
+
+pub(crate) fn record_read(path: &Path, value: &str) {
+    record(Entry::Read, path, value);
+}
+
+pub(crate) fn record_write(path: &Path, value: &str) {
+    record(Entry::Write, path, value);
+}
+
+/// Gets the replay that is active on this thread, if there is one.
+pub(crate) fn active_replay() -> Option<Arc<Mutex<ReplayLog>>> {
+    REPLAY.with_borrow(|replay| replay.clone())
+}
+
+/// Lists the entries of a device class directory, going through the active replay and recorder.
+pub(crate) fn read_dir(dir: &Path) -> Option<Vec<PathBuf>> {
+    if let Some(replay) = active_replay() {
+        return replay
+            .lock()
+            .expect("Tried to use a poisoned lock")
+            .listings
+            .get(dir)
+            .cloned();
+    }
+
+    let entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+
+    for entry in entries.iter() {
+        record(Entry::List, dir, &entry.to_string_lossy());
+    }
+
+    Some(entries)
+}
+
+/// Reads a file that is used to identify a device, going through the active replay and recorder.
+pub(crate) fn read_to_string(path: &Path) -> Ev3Result<String> {
+    if let Some(replay) = active_replay() {
+        return replay
+            .lock()
+            .expect("Tried to use a poisoned lock")
+            .read(path)
+            .ok_or(Ev3Error::InvalidPath);
+    }
+
+    let value = fs::read_to_string(path)
+        .or(Err(Ev3Error::InvalidPath))?
+        .trim()
+        .to_string();
+
+    record_read(path, &value);
+    Ok(value)
+}
+
+/// Records all reads and writes of device attributes to a file while it exists.
+///
+/// Each line of the file contains the time in microseconds since the recording started,
+/// the kind of access (`R` for reads, `W` for writes and `L` for directory listings),
+/// the path of the attribute and the value, separated by tabs.
+/// Simulated devices have no files, so their attributes are recorded under `sim/`
+/// followed by the path that the device would have, like `sim/sys/class/tacho-motor/ev3-ports:outA/position`.
+///
+/// Recordings can be played back with `Replay`.
+///
+/// # Examples
+/// ``` no_run
+/// # fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::replay::Recorder;
+///
+/// // everything is recorded until the recorder is dropped
+/// let recorder = Recorder::start("run.log")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Recorder {
+    id: u64,
+    path: PathBuf,
+}
+
+impl Recorder {
+    /// Starts recording to the given file, replacing its contents.
+    ///
+    /// Only one recording can be active at a time in a process,
+    /// so this stops any previous recording.
+    pub fn start<P>(path: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).map_err(|e| Ev3Error::RecordingFailure {
+            path: path.clone(),
+            os_error: e,
+        })?;
+
+        let id = NEXT_RECORDER_ID.fetch_add(1, Ordering::Relaxed);
+        *RECORDER.lock().expect("Tried to use a poisoned lock") = Some(RecorderState {
+            id,
+            writer: BufWriter::new(file),
+            start: Instant::now(),
+        });
+        RECORDING.store(true, Ordering::Relaxed);
+
+        Ok(Self { id, path })
+    }
+
+    /// Gets the path of the file that is being recorded to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Writes everything that has been recorded so far to the file.
+    ///
+    /// This does nothing if a newer recording has replaced this one.
+    pub fn flush(&self) -> Ev3Result<()> {
+        if let Some(ref mut state) = *RECORDER.lock().expect("Tried to use a poisoned lock")
+            && state.id == self.id
+        {
+            state
+                .writer
+                .flush()
+                .map_err(|e| Ev3Error::RecordingFailure {
+                    path: self.path.clone(),
+                    os_error: e,
+                })?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // only stop the recording if a newer one hasn't replaced it
+        if let Ok(mut recorder) = RECORDER.lock()
+            && recorder.as_ref().is_some_and(|state| state.id == self.id)
+            && let Some(mut state) = recorder.take()
+        {
+            RECORDING.store(false, Ordering::Relaxed);
+            _ = state.writer.flush();
+        }
+    }
+}
+
+#[derive(Default)]
+struct ReadQueue {
+    values: VecDeque<String>,
+    last: Option<String>,
+}
+
+/// A write during a replay that did not match the recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteMismatch {
+    /// The path of the attribute.
+    pub path: PathBuf,
+    /// The value that was recorded, or `None` if the recording had no more writes to this attribute.
+    pub expected: Option<String>,
+    /// The value that was written during the replay.
+    pub actual: String,
+}
+
+#[derive(Default)]
+pub(crate) struct ReplayLog {
+    reads: HashMap<PathBuf, ReadQueue>,
+    writes: HashMap<PathBuf, VecDeque<String>>,
+    listings: HashMap<PathBuf, Vec<PathBuf>>,
+    mismatches: Vec<WriteMismatch>,
+}
+
+impl ReplayLog {
+    fn parse(contents: &str) -> Ev3Result<Self> {
+        let mut log = ReplayLog::default();
+
+        for line in contents.lines().filter(|line| !line.is_empty()) {
+            let mut fields = line.splitn(4, '\t');
+            let (Some(_time), Some(kind), Some(path), Some(value)) =
+                (fields.next(), fields.next(), fields.next(), fields.next())
+            else {
+                return Err(Ev3Error::InvalidValue {
+                    func: "Replay::load".into(),
+                    value: line.into(),
+                });
+            };
+
+            let path = PathBuf::from(unescape(path));
+            let value = unescape(value);
+
+            match kind {
+                "R" => log.reads.entry(path).or_default().values.push_back(value),
+                "W" => log.writes.entry(path).or_default().push_back(value),
+                "L" => log
+                    .listings
+                    .entry(path)
+                    .or_default()
+                    .push(PathBuf::from(value)),
+                _ => {
+                    return Err(Ev3Error::InvalidValue {
+                        func: "Replay::load".into(),
+                        value: line.into(),
+                    });
+                }
+            }
+        }
+
+        Ok(log)
+    }
+
+    // returns the next recorded value, or the last one once the recording runs out
+    pub(crate) fn read(&mut self, path: &Path) -> Option<String> {
+        let queue = self.reads.get_mut(path)?;
+        if let Some(value) = queue.values.pop_front() {
+            queue.last = Some(value);
+        }
+        queue.last.clone()
+    }
+
+    pub(crate) fn write(&mut self, path: &Path, value: &str) {
+        let expected = self
+            .writes
+            .get_mut(path)
+            .and_then(|writes| writes.pop_front());
+
+        if expected.as_deref() != Some(value) {
+            self.mismatches.push(WriteMismatch {
+                path: path.to_path_buf(),
+                expected,
+                actual: value.into(),
+            });
+        }
+    }
+}
+
+/// Plays back a recording made with `Recorder`.
+///
+/// While a `Replay` exists, devices created on the same thread read their attributes
+/// from the recording instead of the real device tree.
+/// Reads are served in the order they were recorded, and once the recording runs out
+/// the last recorded value is repeated.
+/// The recorded timestamps are not used, so a replay runs as fast as the code reads.
+///
+/// Writes are compared against the recorded writes,
+/// which makes it easy to check that your code still behaves the same way.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::replay::Replay;
+///
+/// let replay = Replay::load("run.log")?;
+///
+/// let motor = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+/// motor.run_angle(500, 360).await?;
+///
+/// assert!(replay.mismatched_writes().is_empty());
+/// # Ok(())
+/// # }
+/// ```
+pub struct Replay {
+    log: Arc<Mutex<ReplayLog>>,
+}
+
+impl Replay {
+    /// Loads a recording and starts replaying it on this thread.
+    ///
+    /// This replaces any other replay on this thread.
+    pub fn load<P>(path: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| Ev3Error::RecordingFailure {
+            path: path.to_path_buf(),
+            os_error: e,
+        })?;
+
+        let log = Arc::new(Mutex::new(ReplayLog::parse(&contents)?));
+        REPLAY.with_borrow_mut(|replay| *replay = Some(log.clone()));
+
+        Ok(Self { log })
+    }
+
+    /// Gets all the writes so far that did not match the recording.
+    pub fn mismatched_writes(&self) -> Vec<WriteMismatch> {
+        self.log
+            .lock()
+            .expect("Tried to use a poisoned lock")
+            .mismatches
+            .clone()
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        // try_with because this can run while the thread is shutting down
+        _ = REPLAY.try_with(|replay| {
+            let mut replay = replay.borrow_mut();
+            if replay
+                .as_ref()
+                .is_some_and(|log| Arc::ptr_eq(log, &self.log))
+            {
+                *replay = None;
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::{Direction, MotorPort};
+    use crate::pupdevices::Motor;
+    use crate::sim::SimMotor;
+
+    // there is only one recorder per process, so the tests that record can't run at the same time
+    static RECORDER_TEST: Mutex<()> = Mutex::new(());
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ev3dev-rs-{name}-{}", std::process::id()))
+    }
+
+    fn fake_motor(root: &Path, position: &str) {
+        let motor_dir = root.join("sys/class/tacho-motor/motor0");
+        fs::create_dir_all(&motor_dir).expect("failed to create the fake device tree");
+
+        for (name, value) in [
+            ("address", "ev3-ports:outA"),
+            ("command", ""),
+            ("polarity", "normal"),
+            ("count_per_rot", "360"),
+            ("stop_action", "coast"),
+            ("speed_sp", "0"),
+            ("position", position),
+        ] {
+            fs::write(motor_dir.join(name), value).expect("failed to create a fake attribute");
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Ev3Result<()> {
+        let _lock = RECORDER_TEST.lock().unwrap_or_else(|e| e.into_inner());
+        let root = temp_path("round-trip");
+        let log = temp_path("round-trip.log");
+        fake_motor(&root, "10");
+
+        let recorder = Recorder::start(&log)?;
+        let motor = Motor::new_with_root(MotorPort::OutA, Direction::Clockwise, &root)?;
+        let first = motor.angle()?;
+        fs::write(root.join("sys/class/tacho-motor/motor0/position"), "20")
+            .expect("failed to change a fake attribute");
+        let second = motor.angle()?;
+        motor.run(300)?;
+        drop(motor);
+        drop(recorder);
+
+        // the replay must not need the device tree
+        _ = fs::remove_dir_all(&root);
+
+        let replay = Replay::load(&log)?;
+        let motor = Motor::new_with_root(MotorPort::OutA, Direction::Clockwise, &root)?;
+        assert_eq!((first, second), (10, 20));
+        assert_eq!(motor.angle()?, first);
+        assert_eq!(motor.angle()?, second);
+        // the last value is repeated once the recording runs out
+        assert_eq!(motor.angle()?, second);
+
+        motor.run(300)?;
+        assert!(replay.mismatched_writes().is_empty());
+
+        // a write that wasn't recorded is reported
+        motor.run(500)?;
+        let mismatches = replay.mismatched_writes();
+        assert_eq!(mismatches.len(), 2, "{mismatches:?}");
+        assert_eq!(mismatches[0].expected, None);
+        assert_eq!(mismatches[0].actual, "500");
+
+        drop(motor);
+        drop(replay);
+        _ = fs::remove_file(&log);
+        Ok(())
+    }
+
+    #[test]
+    fn dropping_an_old_recorder_keeps_the_new_one() -> Ev3Result<()> {
+        let _lock = RECORDER_TEST.lock().unwrap_or_else(|e| e.into_inner());
+        let old_log = temp_path("old.log");
+        let new_log = temp_path("new.log");
+
+        let old = Recorder::start(&old_log)?;
+        let new = Recorder::start(&new_log)?;
+        drop(old);
+
+        let sim = SimMotor::new(MotorPort::OutB);
+        let motor = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+        motor.angle()?;
+        drop(motor);
+        drop(sim);
+        drop(new);
+
+        let contents = fs::read_to_string(&new_log).expect("failed to read the recording");
+        // simulated devices are recorded under their own port
+        assert!(
+            contents.contains("\tsim/sys/class/tacho-motor/ev3-ports:outB/position\t"),
+            "{contents}"
+        );
+
+        _ = fs::remove_file(&old_log);
+        _ = fs::remove_file(&new_log);
+        Ok(())
+    }
+
+    #[test]
+    fn escaping_round_trips() {
+        let value = "a\tb\nc\\d";
+        assert_eq!(unescape(&escape(value)), value);
+    }
+}
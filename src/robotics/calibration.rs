@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use fixed::types::I32F32;
+
+use crate::error::{Ev3Error, Ev3Result};
+use crate::robotics::PidGains;
+
+/// The measurements and settings of a `DriveBase`, which can be saved to and loaded from a TOML file.
+///
+/// Get the calibration of a tuned `DriveBase` with `DriveBase::calibration`,
+/// and create a `DriveBase` from it with `DriveBase::from_calibration`.
+///
+/// Only the gains of the PIDs are saved, not the integral deadzone or rate.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::robotics::{Calibration, DriveBase};
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+///
+/// // calibrate once
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+/// drive.settings(300, 600, 200, 400)?;
+/// drive.calibration().save("drive.toml")?;
+///
+/// // and load it in every program after that
+/// let calibration = Calibration::load("drive.toml")?;
+/// let drive = DriveBase::from_calibration(&left, &right, &calibration)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    /// The wheel diameter in mm.
+    pub wheel_diameter: I32F32,
+    /// The distance between the wheels in mm.
+    pub axle_track: I32F32,
+    /// The straight speed in mm/s.
+    pub straight_speed: I32F32,
+    /// The straight acceleration in mm/s².
+    pub straight_acceleration: I32F32,
+    /// The straight deceleration in mm/s².
+    pub straight_deceleration: I32F32,
+    /// The turn rate in degrees per second.
+    pub turn_rate: I32F32,
+    /// The turn acceleration in degrees per second².
+    pub turn_acceleration: I32F32,
+    /// The turn deceleration in degrees per second².
+    pub turn_deceleration: I32F32,
+    /// How close the distance has to get to the target in mm.
+    pub distance_tolerance: I32F32,
+    /// How close the heading has to get to the target in degrees.
+    pub heading_tolerance: I32F32,
+    /// The ramp up setpoint of the motors in ms.
+    pub ramp_up_setpoint: u32,
+    /// The ramp down setpoint of the motors in ms.
+    pub ramp_down_setpoint: u32,
+    /// The gains of the distance PID.
+    pub distance_pid: PidGains,
+    /// The gains of the heading PID.
+    pub heading_pid: PidGains,
+}
+
+impl Calibration {
+    /// Loads a calibration from a TOML file, see `from_toml`.
+    pub fn load<P>(path: P) -> Ev3Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|e| Ev3Error::CalibrationFailure {
+            path: path.to_path_buf(),
+            os_error: e,
+        })?;
+
+        Self::from_toml(&contents)
+    }
+
+    /// Saves the calibration to a TOML file, replacing the file if it exists.
+    pub fn save<P>(&self, path: P) -> Ev3Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml()).map_err(|e| Ev3Error::CalibrationFailure {
+            path: path.to_path_buf(),
+            os_error: e,
+        })
+    }
+
+    /// Converts the calibration to TOML.
+    pub fn to_toml(&self) -> String {
+        let pid = |name: &str, gains: &PidGains| {
+            format!(
+                "[{name}]\nkp = {}\nki = {}\nkd = {}\n",
+                gains.kp, gains.ki, gains.kd
+            )
+        };
+
+        format!(
+            "wheel_diameter = {}\n\
+             axle_track = {}\n\
+             straight_speed = {}\n\
+             straight_acceleration = {}\n\
+             straight_deceleration = {}\n\
+             turn_rate = {}\n\
+             turn_acceleration = {}\n\
+             turn_deceleration = {}\n\
+             distance_tolerance = {}\n\
+             heading_tolerance = {}\n\
+             ramp_up_setpoint = {}\n\
+             ramp_down_setpoint = {}\n\
+             \n{}\n{}",
+            self.wheel_diameter,
+            self.axle_track,
+            self.straight_speed,
+            self.straight_acceleration,
+            self.straight_deceleration,
+            self.turn_rate,
+            self.turn_acceleration,
+            self.turn_deceleration,
+            self.distance_tolerance,
+            self.heading_tolerance,
+            self.ramp_up_setpoint,
+            self.ramp_down_setpoint,
+            pid("distance_pid", &self.distance_pid),
+            pid("heading_pid", &self.heading_pid),
+        )
+    }
+
+    /// Reads a calibration from TOML, like the output of `to_toml`.
+    ///
+    /// Only plain `key = number` lines, `[distance_pid]` and `[heading_pid]` tables and comments are supported.
+    ///
+    /// Returns `InvalidValue` if a line can't be read, if a value is missing, repeated or unknown,
+    /// or if a ramp setpoint isn't a whole number of milliseconds.
+    pub fn from_toml(toml: &str) -> Ev3Result<Self> {
+        let invalid = |value: &str| Ev3Error::InvalidValue {
+            func: "Calibration::from_toml".into(),
+            value: value.into(),
+        };
+
+        // the values by their keys, where the keys in tables start with the table name
+        let mut values = HashMap::new();
+        let mut table = String::new();
+
+        for line in toml.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                table = format!("{}.", name.trim());
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            let key = format!("{table}{}", key.trim());
+            if values.insert(key, value.trim()).is_some() {
+                return Err(invalid(line));
+            }
+        }
+
+        // the setpoints are whole milliseconds, so they are parsed directly instead of as fixed point numbers
+        let mut setpoint = |key: &str| -> Ev3Result<u32> {
+            let value = values.remove(key).ok_or_else(|| invalid(key))?;
+            value.parse().map_err(|_| invalid(value))
+        };
+        let ramp_up_setpoint = setpoint("ramp_up_setpoint")?;
+        let ramp_down_setpoint = setpoint("ramp_down_setpoint")?;
+
+        let mut fixed = |key: &str| -> Ev3Result<I32F32> {
+            let value = values.remove(key).ok_or_else(|| invalid(key))?;
+            value.parse().map_err(|_| invalid(value))
+        };
+
+        let calibration = Self {
+            wheel_diameter: fixed("wheel_diameter")?,
+            axle_track: fixed("axle_track")?,
+            straight_speed: fixed("straight_speed")?,
+            straight_acceleration: fixed("straight_acceleration")?,
+            straight_deceleration: fixed("straight_deceleration")?,
+            turn_rate: fixed("turn_rate")?,
+            turn_acceleration: fixed("turn_acceleration")?,
+            turn_deceleration: fixed("turn_deceleration")?,
+            distance_tolerance: fixed("distance_tolerance")?,
+            heading_tolerance: fixed("heading_tolerance")?,
+            ramp_up_setpoint,
+            ramp_down_setpoint,
+            distance_pid: PidGains {
+                kp: fixed("distance_pid.kp")?,
+                ki: fixed("distance_pid.ki")?,
+                kd: fixed("distance_pid.kd")?,
+            },
+            heading_pid: PidGains {
+                kp: fixed("heading_pid.kp")?,
+                ki: fixed("heading_pid.ki")?,
+                kd: fixed("heading_pid.kd")?,
+            },
+        };
+
+        if let Some(key) = values.keys().next() {
+            return Err(invalid(key));
+        }
+
+        Ok(calibration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calibration() -> Calibration {
+        let gains = |kp: f64, ki: f64, kd: f64| PidGains {
+            kp: I32F32::from_num(kp),
+            ki: I32F32::from_num(ki),
+            kd: I32F32::from_num(kd),
+        };
+
+        Calibration {
+            wheel_diameter: I32F32::from_num(62.4),
+            axle_track: I32F32::from_num(130.5),
+            straight_speed: I32F32::from_num(300),
+            straight_acceleration: I32F32::from_num(600),
+            straight_deceleration: I32F32::from_num(400),
+            turn_rate: I32F32::from_num(200),
+            turn_acceleration: I32F32::from_num(400),
+            turn_deceleration: I32F32::from_num(300),
+            distance_tolerance: I32F32::from_num(2.5),
+            heading_tolerance: I32F32::from_num(1),
+            ramp_up_setpoint: 100,
+            ramp_down_setpoint: 200,
+            distance_pid: gains(10.0, 0.125, -0.3),
+            heading_pid: gains(8.5, 0.0, 1.0 / 3.0),
+        }
+    }
+
+    fn assert_invalid(toml: &str, expected: &str) {
+        match Calibration::from_toml(toml) {
+            Err(Ev3Error::InvalidValue { value, .. }) => assert_eq!(value, expected),
+            other => panic!("expected an invalid {expected}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Ev3Result<()> {
+        let calibration = calibration();
+        assert_eq!(Calibration::from_toml(&calibration.to_toml())?, calibration);
+        Ok(())
+    }
+
+    #[test]
+    fn save_and_load() -> Ev3Result<()> {
+        let path = std::env::temp_dir().join(format!("calibration-{}.toml", std::process::id()));
+        let calibration = calibration();
+
+        calibration.save(&path)?;
+        let loaded = Calibration::load(&path);
+        _ = fs::remove_file(&path);
+
+        assert_eq!(loaded?, calibration);
+        Ok(())
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_ignored() -> Ev3Result<()> {
+        let toml = calibration()
+            .to_toml()
+            .lines()
+            .map(|line| format!("# a comment\n\n  {line}  # the value\n"))
+            .collect::<String>();
+
+        assert_eq!(Calibration::from_toml(&toml)?, calibration());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_key() {
+        let toml = calibration().to_toml().replace("axle_track = 130.5\n", "");
+        assert_invalid(&toml, "axle_track");
+    }
+
+    #[test]
+    fn missing_table() {
+        let toml = calibration().to_toml().replace("[heading_pid]\n", "");
+        // the heading gains are read as repeated distance gains
+        assert_invalid(&toml, "kp = 8.5");
+    }
+
+    #[test]
+    fn duplicate_key() {
+        let toml = format!("turn_rate = 100\n{}", calibration().to_toml());
+        assert_invalid(&toml, "turn_rate = 200");
+    }
+
+    #[test]
+    fn unknown_key() {
+        let toml = format!("{}wheel_count = 2\n", calibration().to_toml());
+        assert_invalid(&toml, "heading_pid.wheel_count");
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let toml = calibration().to_toml();
+        assert_invalid(
+            &toml.replace("axle_track = 130.5", "axle_track 130.5"),
+            "axle_track 130.5",
+        );
+        assert_invalid(
+            &toml.replace("axle_track = 130.5", "axle_track = wide"),
+            "wide",
+        );
+    }
+
+    #[test]
+    fn setpoints_have_to_be_whole_milliseconds() {
+        let toml = calibration().to_toml();
+        assert_invalid(
+            &toml.replace("ramp_up_setpoint = 100", "ramp_up_setpoint = 100.5"),
+            "100.5",
+        );
+        assert_invalid(
+            &toml.replace("ramp_down_setpoint = 200", "ramp_down_setpoint = -200"),
+            "-200",
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::Ev3Result;
+
+// distance sensors only measure every so often, so reading them faster than this just repeats readings
+const SAMPLE_PERIOD: Duration = Duration::from_millis(50);
+
+/// A median filter for distance sensors, which sometimes report spurious readings.
+///
+/// Samples are in mm, where None means that the sensor didn't see anything.
+pub(crate) struct DistanceFilter {
+    samples: VecDeque<Option<f64>>,
+    size: usize,
+    last_sample: Option<Instant>,
+}
+
+impl DistanceFilter {
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(size),
+            size,
+            last_sample: None,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.samples.clear();
+        self.last_sample = None;
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.samples.len() == self.size
+    }
+
+    /// Takes a new sample with `read` if the last one is at least 50 ms old, and returns the filtered distance.
+    pub(crate) fn sample<F>(&mut self, read: F) -> Ev3Result<Option<f64>>
+    where
+        F: FnOnce() -> Ev3Result<Option<f64>>,
+    {
+        let now = Instant::now();
+        if self
+            .last_sample
+            .is_none_or(|last_sample| now - last_sample >= SAMPLE_PERIOD)
+        {
+            self.last_sample = Some(now);
+            if self.is_full() {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(read()?);
+        }
+
+        Ok(self.median())
+    }
+
+    /// The median of the samples, where samples without a reading count as infinitely far away,
+    /// so it is None if most of the samples had no reading.
+    pub(crate) fn median(&self) -> Option<f64> {
+        let mut sorted: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.unwrap_or(f64::INFINITY))
+            .collect();
+        sorted.sort_by(f64::total_cmp);
+
+        sorted
+            .get(sorted.len() / 2)
+            .copied()
+            .filter(|median| median.is_finite())
+    }
+}
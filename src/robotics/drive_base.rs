@@ -1,14 +1,44 @@
-use crate::pid::Pid;
-use crate::pupdevices::GyroSensor;
-use crate::robotics::GyroController;
 use crate::Ev3Error;
-use crate::{parameters::Stop, pupdevices::Motor, Ev3Result};
-use fixed::traits::{LossyInto, ToFixed};
+use crate::Race;
+use crate::pupdevices::{ColorSensor, GyroSensor, InfraredSensor, UltrasonicSensor};
+use crate::robotics::GyroController;
+use crate::robotics::calibration::Calibration;
+use crate::robotics::distance_filter::DistanceFilter;
+use crate::robotics::heading_estimator::HeadingEstimator;
+use crate::robotics::motion_profile::{MotionProfile, ProfileShape};
+use crate::robotics::obstacle_guard::{Clearance, ObstacleGuard, ObstacleSensor};
+use crate::robotics::odometry::{Odometry, Pose, normalize_angle};
+use crate::robotics::pid::{Pid, PidGains};
+use crate::tools::wait_until;
+use crate::{
+    Ev3Result,
+    parameters::{Side, Stop},
+    pupdevices::Motor,
+};
+use fixed::traits::ToFixed;
 use fixed::types::I32F32;
 use scopeguard::defer;
 use std::cell::Cell;
 use std::time::Duration;
-use tokio::time::interval;
+use tokio::time::{Instant, interval};
+
+// a wheel is stalled when its motor works at least this hard (%)
+const STALL_DUTY_CYCLE: i32 = 90;
+
+// while turning slower than this (degrees per second)
+const STALL_SPEED: i32 = 20;
+
+// how fast the robot drives while calibrating the wheel diameter (mm/s)
+const CALIBRATION_SPEED: i32 = 50;
+
+// the reflection (%) that the surface before the lines must at least have, so that the lines stand out from it
+const MIN_BACKGROUND_REFLECTION: u8 = 10;
+
+// how far the robot may drive while looking for the lines, as a multiple of the line spacing
+const LINE_SEARCH_FACTOR: i64 = 2;
+
+// how many readings the distance to the wall is the median of while calibrating the wheel diameter
+const WALL_SAMPLES: usize = 7;
 
 /// A pybricks-like `DriveBase`.
 ///
@@ -38,6 +68,9 @@ use tokio::time::interval;
 /// // default is 500
 /// drive.set_straight_speed(600)?;
 ///
+/// // or set the speeds and accelerations like pybricks, in mm/s, mm/s², deg/s and deg/s²
+/// drive.settings(300, 600, 200, 400)?;
+///
 /// // default should be coast
 /// // unlike pybricks, the stop action doesn't affect whether the robot tracks it's position and heading
 /// drive.set_stop_action(Stop::Hold)?;
@@ -54,8 +87,14 @@ pub struct DriveBase<'a> {
     wheel_diameter: I32F32,
     axle_track: I32F32,
     straight_speed: Cell<I32F32>,
-    turn_speed: Cell<I32F32>,
-    prev_encoder_heading: Cell<I32F32>,
+    straight_acceleration: Cell<I32F32>,
+    straight_deceleration: Cell<I32F32>,
+    turn_rate: Cell<I32F32>,
+    turn_acceleration: Cell<I32F32>,
+    turn_deceleration: Cell<I32F32>,
+    profile_shape: Cell<ProfileShape>,
+    heading_estimator: HeadingEstimator,
+    heading_updated_at: Cell<Option<Instant>>,
     distance_pid: Pid,
     heading_pid: Pid,
     distance_target: Cell<I32F32>,
@@ -64,6 +103,12 @@ pub struct DriveBase<'a> {
     heading_tolerance: Cell<I32F32>,
     using_gyros: Cell<bool>,
     gyros: Option<GyroController<'a>>,
+    obstacle_guard: Option<ObstacleGuard<'a>>,
+    timeout: Option<Duration>,
+    stall_time: Option<Duration>,
+    odometry: Cell<Odometry>,
+    ramp_setpoints: Cell<(u32, u32)>,
+    command: Cell<u64>,
 }
 
 impl<'a> DriveBase<'a> {
@@ -81,11 +126,24 @@ impl<'a> DriveBase<'a> {
     where
         Number: ToFixed,
     {
-        left_motor.set_ramp_up_setpoint(2000)?;
-        right_motor.set_ramp_up_setpoint(2000)?;
+        // the drive actions plan their own acceleration, see `settings`
+        left_motor.set_ramp_up_setpoint(0)?;
+        right_motor.set_ramp_up_setpoint(0)?;
+
+        left_motor.set_ramp_down_setpoint(0)?;
+        right_motor.set_ramp_down_setpoint(0)?;
+
+        let wheel_diameter = I32F32::from_num(wheel_diameter);
+        let axle_track = I32F32::from_num(axle_track);
+
+        // the defaults are the same as the old motor based defaults:
+        // 500 and 550 motor degrees per second, reaching 1000 after 2 seconds
+        let straight = |motor_speed: i32| motor_to_straight(motor_speed, wheel_diameter);
+        let turn = |motor_speed: i32| motor_to_turn(motor_speed, wheel_diameter, axle_track);
 
-        left_motor.set_ramp_down_setpoint(1800)?;
-        right_motor.set_ramp_down_setpoint(1800)?;
+        // the measurements are relative to the start angles, so they start at zero
+        let mut odometry = Odometry::default();
+        odometry.sync(0.0, 0.0);
 
         Ok(Self {
             left_motor,
@@ -93,22 +151,100 @@ impl<'a> DriveBase<'a> {
             left_start_angle: left_motor.angle()?,
             right_start_angle: right_motor.angle()?,
             min_speed: I32F32::from_num(100),
-            wheel_diameter: I32F32::from_num(wheel_diameter),
-            axle_track: I32F32::from_num(axle_track),
-            straight_speed: Cell::new(I32F32::from_num(500)),
-            turn_speed: Cell::new(I32F32::from_num(550)),
-            prev_encoder_heading: Cell::new(I32F32::ZERO),
-            distance_pid: Pid::new(10, 0, 8, 0, 0),
-            heading_pid: Pid::new(10, 0, 5, 0, 0),
+            wheel_diameter,
+            axle_track,
+            straight_speed: Cell::new(straight(500)),
+            straight_acceleration: Cell::new(straight(500)),
+            straight_deceleration: Cell::new(straight(500)),
+            turn_rate: Cell::new(turn(550)),
+            turn_acceleration: Cell::new(turn(500)),
+            turn_deceleration: Cell::new(turn(500)),
+            profile_shape: Cell::new(ProfileShape::Trapezoidal),
+            heading_estimator: HeadingEstimator::new(),
+            heading_updated_at: Cell::new(None),
+            distance_pid: Pid::new(5.0, 0.0, 0.02),
+            heading_pid: Pid::new(5.0, 0.0, 0.01),
             distance_target: Cell::new(I32F32::ZERO),
             heading_target: Cell::new(I32F32::ZERO),
             distance_tolerance: Cell::new(I32F32::from_num(4)),
             heading_tolerance: Cell::new(I32F32::from_num(0.75)),
             using_gyros: Cell::new(false),
             gyros: None,
+            obstacle_guard: None,
+            timeout: Some(Duration::from_secs(2)),
+            stall_time: Some(Duration::from_millis(500)),
+            odometry: Cell::new(odometry),
+            ramp_setpoints: Cell::new((0, 0)),
+            command: Cell::new(0),
         })
     }
 
+    /// Creates a new `DriveBase` with the measurements and settings from a `Calibration`.
+    ///
+    /// Returns `InvalidValue` if a speed, acceleration or tolerance isn't positive.
+    ///
+    /// See `Calibration` for an example.
+    pub fn from_calibration(
+        left_motor: &'a Motor,
+        right_motor: &'a Motor,
+        calibration: &Calibration,
+    ) -> Ev3Result<Self> {
+        let drive = Self::new(
+            left_motor,
+            right_motor,
+            calibration.wheel_diameter,
+            calibration.axle_track,
+        )?;
+
+        drive.settings(
+            calibration.straight_speed,
+            calibration.straight_acceleration,
+            calibration.turn_rate,
+            calibration.turn_acceleration,
+        )?;
+        drive.set_deceleration(
+            calibration.straight_deceleration,
+            calibration.turn_deceleration,
+        )?;
+        drive.set_tolerances(
+            calibration.distance_tolerance,
+            calibration.heading_tolerance,
+        )?;
+        drive.set_ramp_up_setpoint(calibration.ramp_up_setpoint)?;
+        drive.set_ramp_down_setpoint(calibration.ramp_down_setpoint)?;
+
+        let PidGains { kp, ki, kd } = calibration.distance_pid;
+        drive.distance_pid.settings(kp, ki, kd);
+        let PidGains { kp, ki, kd } = calibration.heading_pid;
+        drive.heading_pid.settings(kp, ki, kd);
+
+        Ok(drive)
+    }
+
+    /// Gets the measurements and settings of the `DriveBase`, which can be saved and loaded later.
+    ///
+    /// See `Calibration` for an example.
+    pub fn calibration(&self) -> Calibration {
+        let (ramp_up_setpoint, ramp_down_setpoint) = self.ramp_setpoints.get();
+
+        Calibration {
+            wheel_diameter: self.wheel_diameter,
+            axle_track: self.axle_track,
+            straight_speed: self.straight_speed.get(),
+            straight_acceleration: self.straight_acceleration.get(),
+            straight_deceleration: self.straight_deceleration.get(),
+            turn_rate: self.turn_rate.get(),
+            turn_acceleration: self.turn_acceleration.get(),
+            turn_deceleration: self.turn_deceleration.get(),
+            distance_tolerance: self.distance_tolerance.get(),
+            heading_tolerance: self.heading_tolerance.get(),
+            ramp_up_setpoint,
+            ramp_down_setpoint,
+            distance_pid: self.distance_pid.gains(),
+            heading_pid: self.heading_pid.gains(),
+        }
+    }
+
     /// Adds a single gyro sensor to the `DriveBase`.
     ///
     /// # Examples
@@ -173,35 +309,398 @@ impl<'a> DriveBase<'a> {
         Ok(self)
     }
 
+    /// Sets the `HeadingEstimator` that combines the gyro and the encoders while `use_gyro` is enabled.
+    ///
+    /// The default is `HeadingEstimator::new()`, see its noise parameters for tuning it to the robot.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+    /// use ev3dev_rs::pupdevices::{GyroSensor, Motor};
+    /// use ev3dev_rs::robotics::{DriveBase, HeadingEstimator};
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let gyro = GyroSensor::new(SensorPort::In1)?;
+    ///
+    /// // the wheels slip a lot on this mat
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?
+    ///     .with_gyro(&gyro)?
+    ///     .with_heading_estimator(HeadingEstimator::new().with_wheel_slip(0.2));
+    ///
+    /// drive.use_gyro(true)?;
+    ///
+    /// // the heading is estimated outside of drive actions as well
+    /// println!("heading {}, gyro bias {}", drive.angle()?, drive.heading_estimator().bias());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_heading_estimator(mut self, heading_estimator: HeadingEstimator) -> Self {
+        self.heading_estimator = heading_estimator;
+        self
+    }
+
+    /// Gets the `HeadingEstimator`, for example to read the estimated gyro bias.
+    ///
+    /// It is updated once per control loop tick of the drive actions and followers while `use_gyro` is enabled.
+    /// In between, like in `angle`, `pose` and `state`, the heading is predicted from the gyro and the estimated bias.
+    pub fn heading_estimator(&self) -> &HeadingEstimator {
+        &self.heading_estimator
+    }
+
+    /// Adds a forward facing `UltrasonicSensor` as an obstacle guard.
+    ///
+    /// While driving forward, `straight`, `curve`, `veer`, `drive` and the actions built on them
+    /// slow down once an obstacle is closer than the slow distance,
+    /// and stop with `Ev3Error::Blocked` once it is closer than the stop distance.
+    /// Both distances are in mm.
+    ///
+    /// `LineFollower`, `WallFollower`, `PathFollower` and `RelayTuner` steer the wheels themselves,
+    /// so they aren't guarded.
+    ///
+    /// The readings are filtered to ignore spurious readings, which delays stopping by up to 100 ms,
+    /// so the stop distance should leave some room.
+    ///
+    /// The next drive action starts from where the robot stopped.
+    ///
+    /// Returns `InvalidValue` if a distance is negative.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::Ev3Error;
+    /// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+    /// use ev3dev_rs::pupdevices::{Motor, UltrasonicSensor};
+    /// use ev3dev_rs::robotics::DriveBase;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let ultrasonic_sensor = UltrasonicSensor::new(SensorPort::In2)?;
+    ///
+    /// // slow down within 300 mm of an obstacle, and stop within 100 mm
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?
+    ///     .with_ultrasonic_guard(&ultrasonic_sensor, 100, 300)?;
+    ///
+    /// match drive.straight(1000).await {
+    ///     Err(Ev3Error::Blocked { distance }) => println!("blocked {distance} mm in front"),
+    ///     result => result?,
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_ultrasonic_guard<'b, Number>(
+        self,
+        sensor: &'b UltrasonicSensor,
+        stop_distance: Number,
+        slow_distance: Number,
+    ) -> Ev3Result<Self>
+    where
+        'b: 'a,
+        Number: ToFixed,
+    {
+        self.with_obstacle_guard(
+            ObstacleSensor::Ultrasonic(sensor),
+            "DriveBase::with_ultrasonic_guard",
+            I32F32::from_num(stop_distance),
+            I32F32::from_num(slow_distance),
+        )
+    }
+
+    /// Adds a forward facing `InfraredSensor` as an obstacle guard.
+    ///
+    /// The distances are estimated from the proximity, where 100% is approximately 700 mm,
+    /// so they are only rough.
+    ///
+    /// See `with_ultrasonic_guard`.
+    pub fn with_infrared_guard<'b, Number>(
+        self,
+        sensor: &'b InfraredSensor,
+        stop_distance: Number,
+        slow_distance: Number,
+    ) -> Ev3Result<Self>
+    where
+        'b: 'a,
+        Number: ToFixed,
+    {
+        self.with_obstacle_guard(
+            ObstacleSensor::Infrared(sensor),
+            "DriveBase::with_infrared_guard",
+            I32F32::from_num(stop_distance),
+            I32F32::from_num(slow_distance),
+        )
+    }
+
+    fn with_obstacle_guard(
+        mut self,
+        sensor: ObstacleSensor<'a>,
+        func: &str,
+        stop_distance: I32F32,
+        slow_distance: I32F32,
+    ) -> Ev3Result<Self> {
+        for distance in [stop_distance, slow_distance] {
+            if distance < 0 {
+                return Err(Ev3Error::InvalidValue {
+                    func: func.into(),
+                    value: distance.to_string(),
+                });
+            }
+        }
+
+        self.obstacle_guard = Some(ObstacleGuard::new(
+            sensor,
+            stop_distance.to_num(),
+            slow_distance.to_num(),
+        ));
+        Ok(self)
+    }
+
+    fn reset_obstacle_guard(&self) {
+        if let Some(ref guard) = self.obstacle_guard {
+            guard.reset();
+        }
+    }
+
+    // How much of the planned speed the robot may drive at.
+    // Only driving forward is guarded, and `Blocked` is returned if there is an obstacle in the way.
+    fn clearance(&self, forward: bool) -> Ev3Result<f64> {
+        let Some(ref guard) = self.obstacle_guard else {
+            return Ok(1.0);
+        };
+        if !forward {
+            return Ok(1.0);
+        }
+
+        match guard.check()? {
+            Clearance::Clear(fraction) => Ok(fraction),
+            Clearance::Blocked(distance) => Err(Ev3Error::Blocked {
+                distance: I32F32::from_num(distance),
+            }),
+        }
+    }
+
+    /// Sets how long `straight`, `turn`, `curve` and `veer` may keep correcting after their planned motion
+    /// before they stop with `Ev3Error::TimedOut`.
+    ///
+    /// The planned motion takes as long as the speeds and accelerations allow, see `settings`,
+    /// and the robot normally reaches the target tolerances shortly after.
+    /// If something keeps the robot from getting there, the action would otherwise never end.
+    ///
+    /// `drive` and the actions built on it have no planned motion, so they don't time out,
+    /// and neither do `LineFollower`, `WallFollower`, `PathFollower` and `RelayTuner`.
+    ///
+    /// `None` waits forever. The default is 2 seconds.
+    ///
+    /// The next drive action starts from where the robot stopped.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets how long a wheel may be stalled before a drive action stops with `Ev3Error::Stalled`.
+    ///
+    /// A wheel is stalled when its motor works at a duty cycle of at least 90%
+    /// while it turns slower than 20 degrees per second, like when the robot is pushed into a wall.
+    ///
+    /// This applies to all drive actions, including `drive`,
+    /// but not to `LineFollower`, `WallFollower`, `PathFollower` and `RelayTuner`, which steer the wheels themselves.
+    ///
+    /// `None` turns off stall detection. The default is 500 ms.
+    ///
+    /// The next drive action starts from where the robot stopped.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use std::time::Duration;
+    /// use ev3dev_rs::Ev3Error;
+    /// use ev3dev_rs::parameters::{Direction, MotorPort};
+    /// use ev3dev_rs::pupdevices::Motor;
+    /// use ev3dev_rs::robotics::DriveBase;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?
+    ///     .with_timeout(Some(Duration::from_secs(1)))
+    ///     .with_stall_detection(Some(Duration::from_millis(300)));
+    ///
+    /// // square up against the wall
+    /// match drive.straight(-200).await {
+    ///     Err(Ev3Error::Stalled { .. } | Ev3Error::TimedOut { .. }) => println!("at the wall"),
+    ///     result => result?,
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_stall_detection(mut self, stall_time: Option<Duration>) -> Self {
+        self.stall_time = stall_time;
+        self
+    }
+
+    // Returns `Stalled` once a wheel has been stalled for the stall time.
+    // `stalled_since` is when the wheels started stalling, and has to start as None for each action.
+    fn check_stall(&self, stalled_since: &mut Option<Instant>) -> Ev3Result<()> {
+        let Some(stall_time) = self.stall_time else {
+            return Ok(());
+        };
+
+        let is_stalled = |motor: &Motor| -> Ev3Result<bool> {
+            Ok(motor.duty_cycle()?.abs() >= STALL_DUTY_CYCLE && motor.speed()?.abs() < STALL_SPEED)
+        };
+
+        let side = if is_stalled(self.left_motor)? {
+            Side::Left
+        } else if is_stalled(self.right_motor)? {
+            Side::Right
+        } else {
+            *stalled_since = None;
+            return Ok(());
+        };
+
+        let since = *stalled_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= stall_time {
+            return Err(Ev3Error::Stalled { side });
+        }
+        Ok(())
+    }
+
+    // Ends a drive action with an error.
+    // The next drive action starts from where the robot stopped instead of where it was supposed to be.
+    fn abort(&self, error: Ev3Error) -> Ev3Result<()> {
+        self.sync_targets()?;
+        Err(error)
+    }
+
     /// True makes the `DriveBase` use the gyro, while false makes the `DriveBase` use the motor encoders.
     ///
     /// Using the gyro is highly recommended for accurate drive actions.
+    /// The gyro is combined with the encoders by the `HeadingEstimator`, see `with_heading_estimator`.
     pub fn use_gyro(&self, use_gyro: bool) -> Ev3Result<()> {
         if use_gyro && self.gyros.is_none() {
             return Err(Ev3Error::NoSensorProvided);
         }
+
+        // the heading source changes, so odometry needs new reference measurements
+        self.update_odometry()?;
         self.using_gyros.set(use_gyro);
-        Ok(())
+        self.sync_odometry()
     }
 
     /// Sets the straight speed in motor degrees per second.
     ///
     /// The default is 500 and the max is 1000.
+    ///
+    /// See `settings` to set the speed in mm/s.
     pub fn set_straight_speed<Number>(&self, straight_speed: Number)
     where
         Number: ToFixed,
     {
-        self.straight_speed.set(I32F32::from_num(straight_speed));
+        self.straight_speed
+            .set(motor_to_straight(I32F32::from_num(straight_speed), self.wheel_diameter).abs());
     }
 
     /// Sets the max turn speed in motor degrees per second.
     ///
-    /// The default is 500 and the max is 1000.
+    /// The default is 550 and the max is 1000.
+    ///
+    /// See `settings` to set the turn rate of the robot in degrees per second.
     pub fn set_turn_speed<Number>(&self, turn_speed: Number)
     where
         Number: ToFixed,
     {
-        self.turn_speed.set(I32F32::from_num(turn_speed));
+        self.turn_rate.set(
+            motor_to_turn(
+                I32F32::from_num(turn_speed),
+                self.wheel_diameter,
+                self.axle_track,
+            )
+            .abs(),
+        );
+    }
+
+    /// Sets the speeds and accelerations of the drive actions, like pybricks.
+    ///
+    /// The straight speed is in mm/s, the straight acceleration is in mm/s²,
+    /// the turn rate is in degrees per second and the turn acceleration is in degrees per second².
+    ///
+    /// Every drive action follows a planned motion profile that speeds up with the acceleration,
+    /// cruises at the speed, and slows down with the deceleration.
+    /// This also sets the decelerations to the same values as the accelerations, see `set_deceleration`.
+    ///
+    /// All values must be positive, otherwise `InvalidValue` is returned.
+    pub fn settings<Number>(
+        &self,
+        straight_speed: Number,
+        straight_acceleration: Number,
+        turn_rate: Number,
+        turn_acceleration: Number,
+    ) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        let straight_speed = positive("DriveBase::settings", straight_speed)?;
+        let straight_acceleration = positive("DriveBase::settings", straight_acceleration)?;
+        let turn_rate = positive("DriveBase::settings", turn_rate)?;
+        let turn_acceleration = positive("DriveBase::settings", turn_acceleration)?;
+
+        self.straight_speed.set(straight_speed);
+        self.straight_acceleration.set(straight_acceleration);
+        self.straight_deceleration.set(straight_acceleration);
+        self.turn_rate.set(turn_rate);
+        self.turn_acceleration.set(turn_acceleration);
+        self.turn_deceleration.set(turn_acceleration);
+        Ok(())
+    }
+
+    /// Sets the decelerations of the drive actions, in mm/s² and degrees per second².
+    ///
+    /// Both values must be positive, otherwise `InvalidValue` is returned.
+    pub fn set_deceleration<Number>(
+        &self,
+        straight_deceleration: Number,
+        turn_deceleration: Number,
+    ) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        let straight_deceleration = positive("DriveBase::set_deceleration", straight_deceleration)?;
+        let turn_deceleration = positive("DriveBase::set_deceleration", turn_deceleration)?;
+
+        self.straight_deceleration.set(straight_deceleration);
+        self.turn_deceleration.set(turn_deceleration);
+        Ok(())
+    }
+
+    /// Sets how close the drive actions have to get to their targets, in mm and degrees.
+    ///
+    /// The defaults are 4 mm and 0.75 degrees.
+    ///
+    /// Both values must be positive, otherwise `InvalidValue` is returned.
+    pub fn set_tolerances<Number>(
+        &self,
+        distance_tolerance: Number,
+        heading_tolerance: Number,
+    ) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        let distance_tolerance = positive("DriveBase::set_tolerances", distance_tolerance)?;
+        let heading_tolerance = positive("DriveBase::set_tolerances", heading_tolerance)?;
+
+        self.distance_tolerance.set(distance_tolerance);
+        self.heading_tolerance.set(heading_tolerance);
+        Ok(())
+    }
+
+    /// Sets the shape of the motion profiles.
+    ///
+    /// The default is `ProfileShape::Trapezoidal`.
+    /// `ProfileShape::SCurve` speeds up and slows down more smoothly, which reduces wheel slip.
+    pub fn set_profile_shape(&self, shape: ProfileShape) {
+        self.profile_shape.set(shape);
     }
 
     /// Units are in milliseconds and must be positive.
@@ -210,10 +709,13 @@ impl<'a> DriveBase<'a> {
     ///
     /// This is especially useful for avoiding wheel slip.
     ///
-    /// The default for `DriveBase` motors is 2000.
+    /// The default for `DriveBase` motors is 0, since the drive actions plan their own acceleration.
+    /// See `settings` instead.
     pub fn set_ramp_up_setpoint(&self, sp: u32) -> Ev3Result<()> {
         self.left_motor.set_ramp_up_setpoint(sp)?;
-        self.right_motor.set_ramp_up_setpoint(sp)
+        self.right_motor.set_ramp_up_setpoint(sp)?;
+        self.ramp_setpoints.set((sp, self.ramp_setpoints.get().1));
+        Ok(())
     }
 
     /// Units are in milliseconds and must be positive.
@@ -222,10 +724,13 @@ impl<'a> DriveBase<'a> {
     ///
     /// This is especially useful for avoiding wheel slip.
     ///
-    /// The default for `DriveBase` motors is 1800.
+    /// The default for `DriveBase` motors is 0, since the drive actions plan their own deceleration.
+    /// See `settings` instead.
     pub fn set_ramp_down_setpoint(&self, sp: u32) -> Ev3Result<()> {
         self.left_motor.set_ramp_down_setpoint(sp)?;
-        self.right_motor.set_ramp_down_setpoint(sp)
+        self.right_motor.set_ramp_down_setpoint(sp)?;
+        self.ramp_setpoints.set((self.ramp_setpoints.get().0, sp));
+        Ok(())
     }
     /// Sets the stop action of the `DriveBase`
     ///
@@ -237,7 +742,11 @@ impl<'a> DriveBase<'a> {
 
     /// Sets the distance PID settings
     ///
-    /// default: 10, 0, 8, 0, 0
+    /// The PID corrects the speed in mm/s for each mm that the robot is off the planned distance,
+    /// and is limited to the straight speed. See `Pid` for the gains and `Pid::set_integral_limits`
+    /// for the integral deadzone and rate.
+    ///
+    /// default: 5, 0, 0.02, 0, 0
     pub fn distance_pid_settings<Number>(
         &self,
         kp: Number,
@@ -248,13 +757,17 @@ impl<'a> DriveBase<'a> {
     ) where
         Number: ToFixed,
     {
+        self.distance_pid.settings(kp, ki, kd);
         self.distance_pid
-            .settings(kp, ki, kd, integral_deadzone, integral_rate);
+            .set_integral_limits(integral_deadzone, integral_rate);
     }
 
     /// Sets the heading PID settings
     ///
-    /// default: 10, 0, 5, 0, 0
+    /// The PID corrects the turn rate in degrees per second for each degree that the robot is off the planned heading,
+    /// and is limited to the turn rate. See `distance_pid_settings`.
+    ///
+    /// default: 5, 0, 0.01, 0, 0
     pub fn heading_pid_settings<Number>(
         &self,
         kp: Number,
@@ -265,8 +778,9 @@ impl<'a> DriveBase<'a> {
     ) where
         Number: ToFixed,
     {
+        self.heading_pid.settings(kp, ki, kd);
         self.heading_pid
-            .settings(kp, ki, kd, integral_deadzone, integral_rate);
+            .set_integral_limits(integral_deadzone, integral_rate);
     }
 
     /// Stops the `DriveBase` with the selected stop action.
@@ -274,84 +788,210 @@ impl<'a> DriveBase<'a> {
     /// Async driving functions automatically do this.
     ///
     /// See `set_stop_action` to select the stop action.
+    ///
+    /// This also ends a running `drive`.
     pub fn stop(&self) -> Ev3Result<()> {
+        self.next_command();
         self.left_motor.stop_prev_action()?;
         self.right_motor.stop_prev_action()
     }
 
+    // Stops the `DriveBase` with the given stop action,
+    // and restores the stop action that was selected with `set_stop_action` afterwards
+    pub(crate) fn stop_with(&self, action: Stop) -> Ev3Result<()> {
+        let left = self.left_motor.stop_action()?;
+        let right = self.right_motor.stop_action()?;
+
+        let stopped = self.set_stop_action(action).and_then(|()| self.stop());
+
+        self.left_motor.set_stop_action(left)?;
+        self.right_motor.set_stop_action(right)?;
+        stopped
+    }
+
+    // Turns the motor ramps on or off without forgetting the ramp setpoints.
+    // Controllers that steer continuously turn them off, since the ramps delay every correction.
+    pub(crate) fn enable_ramps(&self, enable: bool) -> Ev3Result<()> {
+        let (up, down) = if enable {
+            self.ramp_setpoints.get()
+        } else {
+            (0, 0)
+        };
+
+        self.left_motor.set_ramp_up_setpoint(up)?;
+        self.right_motor.set_ramp_up_setpoint(up)?;
+        self.left_motor.set_ramp_down_setpoint(down)?;
+        self.right_motor.set_ramp_down_setpoint(down)
+    }
+
+    // The max turn rate of the drive actions in degrees per second
+    pub(crate) fn max_turn_rate(&self) -> f64 {
+        self.turn_rate.get().to_num()
+    }
+
+    // Runs the wheels so that the robot drives at the given speed in mm/s
+    // while turning clockwise at the given rate in degrees per second.
+    // If either wheel would go faster than its max speed, both are slowed down to keep the same curve.
+    pub(crate) fn run_at(&self, speed: f64, turn_rate: f64) -> Ev3Result<()> {
+        let (left_speed, right_speed) = self.wheel_speeds(speed, turn_rate);
+        self.run_wheels(left_speed, right_speed)
+    }
+
+    // Converts a speed in mm/s and a clockwise turn rate in degrees per second
+    // to the motor speeds in degrees per second
+    fn wheel_speeds(&self, speed: f64, turn_rate: f64) -> (f64, f64) {
+        let wheel_circ = std::f64::consts::PI * self.wheel_diameter.to_num::<f64>();
+        let turn_mm = turn_rate.to_radians() * self.axle_track.to_num::<f64>() / 2.0;
+
+        // the heading is clockwise, so turning right means the left wheel goes faster
+        (
+            (speed + turn_mm) * 360.0 / wheel_circ,
+            (speed - turn_mm) * 360.0 / wheel_circ,
+        )
+    }
+
+    fn run_wheels(&self, left_speed: f64, right_speed: f64) -> Ev3Result<()> {
+        let max_speed = self
+            .left_motor
+            .max_speed
+            .min(self.right_motor.max_speed)
+            .to_num::<f64>();
+        let scale = (max_speed / left_speed.abs().max(right_speed.abs())).min(1.0);
+
+        self.left_motor.run((left_speed * scale).round() as i32)?;
+        self.right_motor.run((right_speed * scale).round() as i32)
+    }
+
+    // Starts a new command, which ends a running `drive`
+    fn next_command(&self) -> u64 {
+        let command = self.command.get().wrapping_add(1);
+        self.command.set(command);
+        command
+    }
+
     async fn drive_relative(&self, distance_mm: I32F32, angle_deg: I32F32) -> Ev3Result<()> {
         defer! {
             _ = self.stop()
         }
 
+        self.next_command();
+
         self.distance_pid.reset();
         self.heading_pid.reset();
 
-        let target_distance = self.distance_target.get() + distance_mm;
-        let target_heading = self.heading_target.get() + angle_deg;
+        let start_distance = self.distance_target.get();
+        let start_heading = self.heading_target.get();
+
+        let target_distance = start_distance + distance_mm;
+        let target_heading = start_heading + angle_deg;
 
         self.distance_target.set(target_distance);
         self.heading_target.set(target_heading);
 
+        let shape = self.profile_shape.get();
+        let mut distance_profile = MotionProfile::new(
+            distance_mm,
+            self.straight_speed.get(),
+            self.straight_acceleration.get(),
+            self.straight_deceleration.get(),
+            shape,
+        )?;
+        let mut heading_profile = MotionProfile::new(
+            angle_deg,
+            self.turn_rate.get(),
+            self.turn_acceleration.get(),
+            self.turn_deceleration.get(),
+            shape,
+        )?;
+
+        // both profiles have to finish at the same time, so that curves keep their radius
+        let duration = distance_profile.duration().max(heading_profile.duration());
+        distance_profile.stretch_to(duration);
+        heading_profile.stretch_to(duration);
+
+        // the corrections can't ask for more than the max speeds
+        let straight_speed = self.straight_speed.get();
+        let turn_rate = self.turn_rate.get();
+        self.distance_pid
+            .set_output_limits(-straight_speed, straight_speed)?;
+        self.heading_pid.set_output_limits(-turn_rate, turn_rate)?;
+
         let mut timer = interval(Duration::from_millis(5));
 
         // the first tick completes immediately
         timer.tick().await;
 
-        let straight_speed = self.straight_speed.get();
-        let turn_speed = self.turn_speed.get();
+        let forward = distance_mm > 0;
+        self.reset_obstacle_guard();
+
+        // the time along the profiles, which runs slower while slowing down for an obstacle
+        let mut elapsed = Duration::ZERO;
+        let mut last_tick = Instant::now();
+
+        // when the profiles finished, for the timeout
+        let mut done_at: Option<Instant> = None;
+        let mut stalled_since = None;
 
         loop {
-            let left_angle = I32F32::from_num(self.left_motor.angle()? - self.left_start_angle);
-            let right_angle = I32F32::from_num(self.right_motor.angle()? - self.right_start_angle);
-            let current_distance = self.encoders_to_distance(left_angle, right_angle);
-            let current_heading = if self.using_gyros.get()
-                && let Some(ref gyro) = self.gyros
-            {
-                let encoders = self.encoders_to_heading()?;
-                I32F32::from_num(gyro.heading()?) * I32F32::from_num(0.9)
-                    + encoders * I32F32::from_num(0.1)
-            } else {
-                self.encoders_to_heading()?
+            let now = Instant::now();
+            self.tick(now)?;
+
+            let clearance = match self.clearance(forward) {
+                Ok(clearance) => clearance,
+                Err(e) => return self.abort(e),
             };
 
-            let distance_error = target_distance - current_distance;
-            let heading_error = target_heading - current_heading;
+            if let Err(e) = self.check_stall(&mut stalled_since) {
+                return self.abort(e);
+            }
+
+            let dt = now - last_tick;
+            elapsed += dt.mul_f64(clearance);
+            last_tick = now;
+
+            let profile_done = elapsed >= duration;
 
-            if distance_error.abs() < self.distance_tolerance.get()
-                && heading_error.abs() < self.heading_tolerance.get()
+            let current_distance = self.current_distance()?;
+            let current_heading = self.current_heading()?;
+
+            if profile_done
+                && (target_distance - current_distance).abs() < self.distance_tolerance.get()
+                && (target_heading - current_heading).abs() < self.heading_tolerance.get()
             {
                 break;
             }
 
-            let dive_effort = self.distance_pid.next(distance_error);
-            let turn_effort = -self.heading_pid.next(heading_error);
+            if profile_done
+                && let Some(timeout) = self.timeout
+                && now - *done_at.get_or_insert(now) >= timeout
+            {
+                return self.abort(Ev3Error::TimedOut { timeout });
+            }
 
-            let drive_speed_out = dive_effort * straight_speed;
-            let turn_speed_out = turn_effort * turn_speed;
+            // follow the planned speeds, and correct for how far the robot is ahead of the planned positions
+            let distance_ahead =
+                current_distance - (start_distance + distance_profile.position(elapsed));
+            let heading_ahead =
+                current_heading - (start_heading + heading_profile.position(elapsed));
 
-            let left_speed = (drive_speed_out - turn_speed_out)
-                .clamp(-self.right_motor.max_speed, self.left_motor.max_speed);
+            let drive_correction = self.distance_pid.update(distance_ahead, dt);
+            let turn_correction = self.heading_pid.update(heading_ahead, dt);
 
-            let right_speed = (drive_speed_out + turn_speed_out)
-                .clamp(-self.left_motor.max_speed, self.right_motor.max_speed);
+            let t = elapsed.as_secs_f64();
+            let (left_speed, right_speed) = self.wheel_speeds(
+                distance_profile.speed_f64(t) * clearance + drive_correction.to_num::<f64>(),
+                heading_profile.speed_f64(t) * clearance + turn_correction.to_num::<f64>(),
+            );
 
-            self.left_motor.run(
-                (if left_speed.abs() < self.min_speed {
-                    self.min_speed * left_speed.signum()
-                } else {
-                    left_speed
-                })
-                .lossy_into(),
-            )?;
-            self.right_motor.run(
-                (if right_speed.abs() < self.min_speed {
-                    self.min_speed * right_speed.signum()
-                } else {
-                    right_speed
-                })
-                .lossy_into(),
-            )?;
+            if profile_done {
+                // once the robot is close, the corrections can be too small to move the motors at all
+                self.run_wheels(
+                    self.at_least_min_speed(left_speed),
+                    self.at_least_min_speed(right_speed),
+                )?;
+            } else {
+                self.run_wheels(left_speed, right_speed)?;
+            }
 
             timer.tick().await;
         }
@@ -359,6 +999,35 @@ impl<'a> DriveBase<'a> {
         Ok(())
     }
 
+    fn at_least_min_speed(&self, speed: f64) -> f64 {
+        let min_speed = self.min_speed.to_num::<f64>();
+        if speed != 0.0 && speed.abs() < min_speed {
+            min_speed.copysign(speed)
+        } else {
+            speed
+        }
+    }
+
+    // The distance that the drive actions measure their targets against
+    fn current_distance(&self) -> Ev3Result<I32F32> {
+        let left_angle = I32F32::from_num(self.left_motor.angle()? - self.left_start_angle);
+        let right_angle = I32F32::from_num(self.right_motor.angle()? - self.right_start_angle);
+        Ok(self.encoders_to_distance(left_angle, right_angle))
+    }
+
+    // The heading that the drive actions measure their targets against
+    pub(crate) fn current_heading(&self) -> Ev3Result<I32F32> {
+        Ok(
+            if self.using_gyros.get()
+                && let Some(ref gyro) = self.gyros
+            {
+                self.estimate_heading(gyro)?
+            } else {
+                self.encoders_to_heading()?
+            },
+        )
+    }
+
     /// Drives straight by the given distance in mm.
     pub async fn straight<Number>(&self, distance: Number) -> Ev3Result<()>
     where
@@ -405,34 +1074,472 @@ impl<'a> DriveBase<'a> {
             .await
     }
 
-    /// Experimental function to find the best axle track for the robot
+    /// Drives at the given speed in mm/s while turning clockwise at the given rate in degrees per second.
     ///
-    /// This should print out the ideal axle track once it is finished testing.
+    /// This keeps driving until `stop` is called, another drive action starts, or it is canceled,
+    /// so it can be raced against a sensor condition with `select!`.
+    /// It returns `Ok` when it is ended by `stop` or another drive action,
+    /// and stops the `DriveBase` when it is canceled.
     ///
-    /// If you are having trouble with inaccurate heading readings due to wheel slipping, see `set_ramp_up_setpoint`.
+    /// The heading is held on course with the heading PID, using the gyro if `use_gyro` is enabled,
+    /// so driving with a turn rate of 0 keeps the robot straight.
     ///
-    /// Note that the output value can vary wildly based on surface.
-    pub async fn find_calibrated_axle_track<Number>(
-        &mut self,
-        margin_of_error: Number,
-    ) -> Ev3Result<I32F32>
+    /// Returns `Blocked` if the obstacle guard stops the robot, see `with_ultrasonic_guard`,
+    /// and `Stalled` if a wheel is stalled, see `with_stall_detection`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use std::time::Duration;
+    /// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+    /// use ev3dev_rs::pupdevices::{Motor, TouchSensor};
+    /// use ev3dev_rs::robotics::DriveBase;
+    /// use ev3dev_rs::select;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+    /// let touch = TouchSensor::new(SensorPort::In1)?;
+    ///
+    /// // drive forward until the touch sensor is pressed
+    /// select!(drive.drive(200, 0), async {
+    ///     while !touch.pressed()? {
+    ///         tokio::time::sleep(Duration::from_millis(10)).await;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn drive<Number>(&self, speed: Number, turn_rate: Number) -> Ev3Result<()>
     where
         Number: ToFixed,
     {
-        self.use_gyro(true)?;
-        let fixed_estimate = I32F32::from_num(self.axle_track);
-        let fixed_margin_of_error = I32F32::from_num(margin_of_error);
-        let resphi = I32F32::FRAC_1_PHI;
+        let speed = I32F32::from_num(speed);
+        let turn_rate = I32F32::from_num(turn_rate);
 
-        let mut a = fixed_estimate - fixed_margin_of_error;
-        let mut b = fixed_estimate + fixed_margin_of_error;
-        let tolerance = I32F32::from_num(0.5);
+        let command = self.next_command();
 
-        // Initial test points
-        let mut x1 = a + resphi * (b - a);
-        let mut x2 = b - resphi * (b - a);
+        defer! {
+            // a newer command already took over the motors
+            if self.command.get() == command {
+                _ = self.stop();
+            }
+        }
 
-        let mut f1 = self.test_axle_track(x1).await?;
+        self.heading_pid.reset();
+
+        let max_turn_rate = self.turn_rate.get();
+        self.heading_pid
+            .set_output_limits(-max_turn_rate, max_turn_rate)?;
+
+        let start_heading = self.heading_target.get();
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        let forward = speed > 0;
+        self.reset_obstacle_guard();
+
+        // the driving time, which runs slower while slowing down for an obstacle
+        let mut elapsed = Duration::ZERO;
+        let mut last_tick = Instant::now();
+
+        let mut stalled_since = None;
+
+        while self.command.get() == command {
+            let now = Instant::now();
+            self.tick(now)?;
+
+            let clearance = match self.clearance(forward) {
+                Ok(clearance) => clearance,
+                Err(e) => return self.abort(e),
+            };
+
+            if let Err(e) = self.check_stall(&mut stalled_since) {
+                return self.abort(e);
+            }
+
+            let dt = now - last_tick;
+            elapsed += dt.mul_f64(clearance);
+            last_tick = now;
+
+            // the heading that the robot should have turned to by now
+            let heading_target =
+                start_heading + turn_rate * I32F32::from_num(elapsed.as_secs_f64());
+            self.heading_target.set(heading_target);
+
+            // the next drive action continues from where the robot stopped
+            self.distance_target.set(self.current_distance()?);
+
+            let turn_correction = self
+                .heading_pid
+                .update(self.current_heading()? - heading_target, dt);
+
+            self.run_at(
+                speed.to_num::<f64>() * clearance,
+                (turn_rate * I32F32::from_num(clearance) + turn_correction).to_num(),
+            )?;
+
+            timer.tick().await;
+        }
+
+        Ok(())
+    }
+
+    /// Drives straight forward at the straight speed until the condition completes,
+    /// and returns the distance travelled in mm.
+    ///
+    /// The condition can be any future that returns an `Ev3Result`, like a `Motor` action,
+    /// or a closure wrapped in `tools::wait_until`.
+    ///
+    /// The `DriveBase` is stopped with the selected stop action once the condition completes.
+    /// Any error from the condition is returned.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+    /// use ev3dev_rs::pupdevices::{ColorSensor, Motor};
+    /// use ev3dev_rs::robotics::DriveBase;
+    /// use ev3dev_rs::tools::wait_until;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+    /// let arm = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+    /// let color_sensor = ColorSensor::new(SensorPort::In1)?;
+    ///
+    /// // drive to the black line
+    /// let distance = drive
+    ///     .straight_until(wait_until(|| Ok(color_sensor.reflection()? < 15)))
+    ///     .await?;
+    /// println!("the line is {distance} mm away");
+    ///
+    /// // back up slowly until the arm is raised
+    /// drive.drive_until(-50, 0, arm.run_target(300, 90)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn straight_until<C>(&self, condition: C) -> Ev3Result<I32F32>
+    where
+        C: Future<Output = Ev3Result<()>>,
+    {
+        self.drive_until(self.straight_speed.get(), I32F32::ZERO, condition)
+            .await
+    }
+
+    /// Drives at the given speed in mm/s while turning clockwise at the given rate in degrees per second
+    /// until the condition completes, and returns the distance travelled in mm.
+    ///
+    /// The distance is negative when driving backwards.
+    ///
+    /// See `straight_until` and `drive`.
+    pub async fn drive_until<Number, C>(
+        &self,
+        speed: Number,
+        turn_rate: Number,
+        condition: C,
+    ) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+        C: Future<Output = Ev3Result<()>>,
+    {
+        let start = self.current_distance()?;
+
+        // drive stops the DriveBase when it is canceled by the condition
+        (self.drive(speed, turn_rate), condition).race().await?;
+
+        self.update_odometry()?;
+        Ok(self.current_distance()? - start)
+    }
+
+    /// Gets the estimated pose of the robot.
+    ///
+    /// The pose is tracked continuously from the wheel encoders,
+    /// and from the gyro instead of the encoders for the heading when `use_gyro` is enabled.
+    ///
+    /// The starting pose is `(0, 0, 0)`, see `reset_pose`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::parameters::{Direction, MotorPort};
+    /// use ev3dev_rs::pupdevices::Motor;
+    /// use ev3dev_rs::robotics::DriveBase;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+    ///
+    /// drive.straight(300).await?;
+    /// drive.turn(90).await?;
+    /// drive.straight(200).await?;
+    ///
+    /// // about x = 300, y = 200, heading = 90
+    /// let pose = drive.pose()?;
+    /// println!("{}, {}, {}", pose.x, pose.y, pose.heading);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pose(&self) -> Ev3Result<Pose> {
+        self.update_odometry()?;
+        Ok(self.odometry.get().pose())
+    }
+
+    /// Sets the estimated pose of the robot, and resets `distance` to zero.
+    ///
+    /// The position is in mm and the heading is in degrees.
+    ///
+    /// This doesn't affect the targets of the drive actions.
+    pub fn reset_pose<Number>(&self, x: Number, y: Number, heading: Number) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        self.update_odometry()?;
+        let mut odometry = self.odometry.get();
+        odometry.reset(Pose::new(x, y, heading));
+        self.odometry.set(odometry);
+        Ok(())
+    }
+
+    /// Gets the distance driven since the last `reset_pose` in mm.
+    ///
+    /// Driving backwards reduces the distance.
+    pub fn distance(&self) -> Ev3Result<I32F32> {
+        self.update_odometry()?;
+        Ok(self.odometry.get().distance())
+    }
+
+    /// Gets the heading of the robot in degrees.
+    ///
+    /// This is the same as the heading of the `pose`.
+    pub fn angle(&self) -> Ev3Result<I32F32> {
+        Ok(self.pose()?.heading)
+    }
+
+    /// Gets the state of the robot like pybricks,
+    /// as `(distance, drive_speed, angle, turn_rate)`.
+    ///
+    /// The distance is in mm, the drive speed is in mm/s,
+    /// the angle is in degrees and the turn rate is in degrees per second.
+    pub fn state(&self) -> Ev3Result<(I32F32, I32F32, I32F32, I32F32)> {
+        let distance = self.distance()?;
+        let angle = self.angle()?;
+
+        let left_speed = I32F32::from_num(self.left_motor.speed()?);
+        let right_speed = I32F32::from_num(self.right_motor.speed()?);
+        let drive_speed = self.encoders_to_distance(left_speed, right_speed);
+
+        let turn_rate = if self.using_gyros.get()
+            && let Some(ref gyro) = self.gyros
+        {
+            gyro.angular_velocity()? - self.heading_estimator.bias()
+        } else {
+            self.encoders_to_raw_heading(left_speed, right_speed)
+        };
+
+        Ok((distance, drive_speed, angle, turn_rate))
+    }
+
+    // Reads the wheel distance and heading that the odometry is integrated from
+    fn odometry_measurements(&self) -> Ev3Result<(f64, f64)> {
+        let left_deg = I32F32::from_num(self.left_motor.angle()? - self.left_start_angle);
+        let right_deg = I32F32::from_num(self.right_motor.angle()? - self.right_start_angle);
+
+        let distance = self.encoders_to_distance(left_deg, right_deg);
+        let heading = if self.using_gyros.get()
+            && let Some(ref gyro) = self.gyros
+        {
+            self.estimate_heading(gyro)?
+        } else {
+            self.encoders_to_raw_heading(left_deg, right_deg)
+        };
+
+        Ok((distance.to_num(), heading.to_num()))
+    }
+
+    // Predicts the estimated heading from the gyro since the last tick, without fusing the readings
+    fn estimate_heading(&self, gyro: &GyroController) -> Ev3Result<I32F32> {
+        let elapsed = self
+            .heading_updated_at
+            .get()
+            .map_or(Duration::ZERO, |updated_at| updated_at.elapsed());
+
+        Ok(self.heading_estimator.predict(gyro.heading()?, elapsed))
+    }
+
+    // Fuses the gyro and the encoders into the heading estimate and updates the odometry.
+    // Control loops call this once at the start of every tick, so each reading is fused exactly once,
+    // with the time since the previous tick.
+    pub(crate) fn tick(&self, now: Instant) -> Ev3Result<()> {
+        if self.using_gyros.get()
+            && let Some(ref gyro) = self.gyros
+        {
+            let dt = self
+                .heading_updated_at
+                .get()
+                .map_or(Duration::ZERO, |updated_at| {
+                    now.saturating_duration_since(updated_at)
+                });
+            self.heading_updated_at.set(Some(now));
+
+            self.heading_estimator
+                .update(gyro.heading()?, self.encoders_to_heading()?, dt);
+        }
+
+        self.update_odometry()
+    }
+
+    fn update_odometry(&self) -> Ev3Result<()> {
+        let (distance, heading) = self.odometry_measurements()?;
+        let mut odometry = self.odometry.get();
+        odometry.update(distance, heading);
+        self.odometry.set(odometry);
+        Ok(())
+    }
+
+    fn sync_odometry(&self) -> Ev3Result<()> {
+        // the measurements may have jumped, so the heading estimator starts over from them
+        self.heading_estimator.reset();
+        self.heading_updated_at.set(None);
+
+        let (distance, heading) = self.odometry_measurements()?;
+        let mut odometry = self.odometry.get();
+        odometry.sync(distance, heading);
+        self.odometry.set(odometry);
+        Ok(())
+    }
+
+    /// Drives to the given position on the field in mm, see `pose`.
+    ///
+    /// The robot first turns towards the position, then drives straight to it
+    /// while correcting any heading error left over from the turn.
+    ///
+    /// The robot ends up facing away from where it started, see `drive_to_pose` to also set the heading.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::parameters::{Direction, MotorPort};
+    /// use ev3dev_rs::pupdevices::Motor;
+    /// use ev3dev_rs::robotics::DriveBase;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+    ///
+    /// // the robot starts in the corner of the field, facing along the long side
+    /// drive.reset_pose(150, 100, 0)?;
+    ///
+    /// drive.drive_to(800, 400).await?;
+    /// drive.drive_to_pose(1200, 400, -90).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn drive_to<Number>(&self, x: Number, y: Number) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        self.navigate_to(I32F32::from_num(x).to_num(), I32F32::from_num(y).to_num())
+            .await
+    }
+
+    /// Drives to the given position on the field in mm, then turns to the given heading in degrees.
+    ///
+    /// See `drive_to`.
+    pub async fn drive_to_pose<Number>(
+        &self,
+        x: Number,
+        y: Number,
+        heading: Number,
+    ) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        self.navigate_to(I32F32::from_num(x).to_num(), I32F32::from_num(y).to_num())
+            .await?;
+
+        let pose = self.pose()?;
+        let turn = normalize_angle(
+            I32F32::from_num(heading).to_num::<f64>() - pose.heading.to_num::<f64>(),
+        );
+
+        self.sync_targets()?;
+        self.drive_relative(I32F32::ZERO, I32F32::from_num(turn))
+            .await
+    }
+
+    async fn navigate_to(&self, x: f64, y: f64) -> Ev3Result<()> {
+        // the direction and distance to the position from the current pose
+        let course = || -> Ev3Result<(f64, f64)> {
+            let pose = self.pose()?;
+            let dx = x - pose.x.to_num::<f64>();
+            let dy = y - pose.y.to_num::<f64>();
+            let turn = normalize_angle(dy.atan2(dx).to_degrees() - pose.heading.to_num::<f64>());
+            Ok((turn, dx.hypot(dy)))
+        };
+
+        let (turn, distance) = course()?;
+        if distance < self.distance_tolerance.get().to_num::<f64>() {
+            return Ok(());
+        }
+
+        // the targets are in the measurement frame, while the course is in the pose frame,
+        // so the targets have to start from the current measurements
+        self.sync_targets()?;
+        self.drive_relative(I32F32::ZERO, I32F32::from_num(turn))
+            .await?;
+
+        let (turn, distance) = course()?;
+        self.sync_targets()?;
+        self.drive_relative(I32F32::from_num(distance), I32F32::from_num(turn))
+            .await
+    }
+
+    // Moves the targets to the current measurements, so that the next drive action
+    // is relative to where the robot actually is instead of where it was supposed to be
+    pub(crate) fn sync_targets(&self) -> Ev3Result<()> {
+        self.distance_target.set(self.current_distance()?);
+        self.heading_target.set(self.current_heading()?);
+        Ok(())
+    }
+
+    /// Experimental function to find the best axle track for the robot
+    ///
+    /// This returns the ideal axle track once it is finished testing.
+    /// The `DriveBase` uses the ideal axle track afterwards, so it is included in `calibration`.
+    ///
+    /// If you are having trouble with inaccurate heading readings due to wheel slipping, see `set_ramp_up_setpoint`.
+    ///
+    /// Note that the output value can vary wildly based on surface.
+    pub async fn find_calibrated_axle_track<Number>(
+        &mut self,
+        margin_of_error: Number,
+    ) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+    {
+        self.use_gyro(true)?;
+        let fixed_estimate = I32F32::from_num(self.axle_track);
+        let fixed_margin_of_error = I32F32::from_num(margin_of_error);
+        let resphi = I32F32::FRAC_1_PHI;
+
+        let mut a = fixed_estimate - fixed_margin_of_error;
+        let mut b = fixed_estimate + fixed_margin_of_error;
+        let tolerance = I32F32::from_num(0.5);
+
+        // Initial test points
+        let mut x1 = a + resphi * (b - a);
+        let mut x2 = b - resphi * (b - a);
+
+        let mut f1 = self.test_axle_track(x1).await?;
         let mut f2 = self.test_axle_track(x2).await?;
 
         // Golden section search loop
@@ -455,20 +1562,23 @@ impl<'a> DriveBase<'a> {
         }
 
         let best = (a + b) / I32F32::from_num(2);
-        println!("Best axle track: {}", best);
+        self.axle_track = best;
 
         Ok(best)
     }
 
     // Helper function to test a single axle track value
     async fn test_axle_track(&mut self, candidate: I32F32) -> Ev3Result<I32F32> {
-        println!("testing {}", candidate);
         self.axle_track = candidate;
 
         // Reset to known position
         if let Some(ref gyros) = self.gyros {
             let start_encoder_heading = self.encoders_to_heading()?;
+            self.update_odometry()?;
             gyros.reset()?;
+            self.sync_odometry()?;
+            // the gyro reset moved the heading, so the turn has to start from the new heading
+            self.sync_targets()?;
             // Do a test turn (90 degrees)
             self.turn(90).await?;
 
@@ -476,15 +1586,163 @@ impl<'a> DriveBase<'a> {
             let gyro_turned = gyros.heading()?;
             let encoder_turned = self.encoders_to_heading()? - start_encoder_heading;
 
-            let error = (gyro_turned - encoder_turned).abs();
-            println!("Error: {}", error);
-
-            Ok(error)
+            Ok((gyro_turned - encoder_turned).abs())
         } else {
             Err(Ev3Error::NoSensorProvided)
         }
     }
 
+    /// Finds the effective wheel diameter by driving over two lines with a `ColorSensor` in reflection mode,
+    /// and returns it in mm.
+    ///
+    /// The robot has to start on the background before the first line, facing the lines.
+    /// It drives forward slowly until the sensor reaches the second line,
+    /// so the line spacing is the distance in mm from the near edge of the first line to the near edge of the second.
+    /// A longer spacing gives a more accurate result.
+    ///
+    /// The sensor sees a line when the reflection drops below half of the reflection at the start.
+    ///
+    /// Returns `InvalidValue` if the reflection at the start is below 10%,
+    /// or if the robot drives twice the line spacing without finding both lines.
+    ///
+    /// The `DriveBase` uses the new wheel diameter afterwards, so it is included in `calibration`.
+    ///
+    /// # Examples
+    ///
+    /// ``` no_run
+    /// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+    /// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+    /// use ev3dev_rs::pupdevices::{ColorSensor, Motor};
+    /// use ev3dev_rs::robotics::DriveBase;
+    ///
+    /// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+    /// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+    /// let mut drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+    /// let color_sensor = ColorSensor::new(SensorPort::In1)?;
+    ///
+    /// // two lines that start 500 mm apart
+    /// let wheel_diameter = drive.find_wheel_diameter_with_lines(&color_sensor, 500).await?;
+    /// println!("the wheels are {wheel_diameter} mm");
+    ///
+    /// drive.calibration().save("drive.toml")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn find_wheel_diameter_with_lines<Number>(
+        &mut self,
+        sensor: &ColorSensor,
+        line_spacing: Number,
+    ) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+    {
+        let func = "DriveBase::find_wheel_diameter_with_lines";
+        let line_spacing = positive(func, line_spacing)?;
+
+        let background = sensor.reflection()?;
+        if background < MIN_BACKGROUND_REFLECTION {
+            return Err(Ev3Error::InvalidValue {
+                func: func.into(),
+                value: format!("background reflection {background}"),
+            });
+        }
+
+        let threshold = background / 2;
+        let on_line = || Ok(sensor.reflection()? < threshold);
+
+        // the distance between the lines with the current wheel diameter
+        let measured = Cell::new(I32F32::ZERO);
+
+        let search = async {
+            wait_until(on_line).await?;
+            let start = self.current_distance()?;
+
+            wait_until(|| Ok(!on_line()?)).await?;
+            wait_until(on_line).await?;
+            measured.set(self.current_distance()? - start);
+            Ok(())
+        };
+
+        let start = self.current_distance()?;
+        let max_distance = line_spacing.saturating_mul_int(LINE_SEARCH_FACTOR);
+        let too_far = async {
+            wait_until(|| Ok(self.current_distance()? - start > max_distance)).await?;
+            Err(Ev3Error::InvalidValue {
+                func: func.into(),
+                value: format!("no lines within {max_distance} mm"),
+            })
+        };
+
+        self.drive_until(CALIBRATION_SPEED, 0, (search, too_far).race())
+            .await?;
+
+        self.set_wheel_diameter(func, line_spacing, measured.get())
+    }
+
+    /// Finds the effective wheel diameter by driving towards a wall with a forward facing `UltrasonicSensor`,
+    /// and returns it in mm.
+    ///
+    /// The robot has to start facing the wall, further away from it than the given distance.
+    /// It measures the distance to the wall, drives straight forward by the given distance in mm,
+    /// and measures the distance to the wall again.
+    /// A longer distance gives a more accurate result, as long as the sensor can still see the wall.
+    ///
+    /// Returns `InvalidValue` if the sensor doesn't see the wall.
+    ///
+    /// The `DriveBase` uses the new wheel diameter afterwards, so it is included in `calibration`.
+    pub async fn find_wheel_diameter_with_wall<Number>(
+        &mut self,
+        sensor: &UltrasonicSensor,
+        distance: Number,
+    ) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+    {
+        let distance = positive("DriveBase::find_wheel_diameter_with_wall", distance)?;
+        let no_wall = || Ev3Error::InvalidValue {
+            func: "DriveBase::find_wheel_diameter_with_wall".into(),
+            value: "no wall".into(),
+        };
+
+        let before = wall_distance(sensor).await?.ok_or_else(no_wall)?;
+        let start = self.current_distance()?;
+
+        self.straight(distance).await?;
+
+        // the distance with the current wheel diameter
+        let measured = self.current_distance()? - start;
+        let after = wall_distance(sensor).await?.ok_or_else(no_wall)?;
+
+        self.set_wheel_diameter(
+            "DriveBase::find_wheel_diameter_with_wall",
+            I32F32::from_num(before - after),
+            measured,
+        )
+    }
+
+    // Scales the wheel diameter so that the measured distance becomes the actual distance,
+    // without moving the pose or the targets
+    fn set_wheel_diameter(
+        &mut self,
+        func: &str,
+        actual: I32F32,
+        measured: I32F32,
+    ) -> Ev3Result<I32F32> {
+        let wheel_diameter = self
+            .wheel_diameter
+            .checked_mul(actual)
+            .and_then(|distance| distance.checked_div(measured))
+            .unwrap_or(I32F32::ZERO);
+        let wheel_diameter = positive(func, wheel_diameter)?;
+
+        self.update_odometry()?;
+        self.wheel_diameter = wheel_diameter;
+        self.sync_odometry()?;
+        self.sync_targets()?;
+
+        Ok(wheel_diameter)
+    }
+
     // Convert encoder positions to distance traveled (average of both wheels)
     fn encoders_to_distance(&self, left_deg: I32F32, right_deg: I32F32) -> I32F32 {
         let wheel_circ = I32F32::PI * self.wheel_diameter;
@@ -493,22 +1751,466 @@ impl<'a> DriveBase<'a> {
         (left_mm + right_mm) / 2
     }
 
-    // Convert encoder positions to heading (differential between wheels)
-    fn encoders_to_heading(&self) -> Ev3Result<I32F32> {
-        let left_deg = I32F32::from_num(self.left_motor.angle()? - self.left_start_angle);
-        let right_deg = I32F32::from_num(self.right_motor.angle()? - self.right_start_angle);
-
+    // Convert encoder positions (or speeds) to an unfiltered heading (or turn rate)
+    fn encoders_to_raw_heading(&self, left_deg: I32F32, right_deg: I32F32) -> I32F32 {
         let wheel_circ = I32F32::PI * self.wheel_diameter;
         let left_mm = wheel_circ * left_deg / 360;
         let right_mm = wheel_circ * right_deg / 360;
         let arc_diff = left_mm - right_mm;
         let turn_rad = arc_diff / self.axle_track;
-        let raw_heading = turn_rad * 180 / I32F32::PI;
+        turn_rad * 180 / I32F32::PI
+    }
 
-        let alpha = I32F32::from_num(0.7);
-        let filtered =
-            alpha * raw_heading + (I32F32::from_num(1) - alpha) * self.prev_encoder_heading.get();
-        self.prev_encoder_heading.set(filtered);
-        Ok(filtered)
+    // Convert encoder positions to heading (differential between wheels)
+    fn encoders_to_heading(&self) -> Ev3Result<I32F32> {
+        let left_deg = I32F32::from_num(self.left_motor.angle()? - self.left_start_angle);
+        let right_deg = I32F32::from_num(self.right_motor.angle()? - self.right_start_angle);
+
+        Ok(self.encoders_to_raw_heading(left_deg, right_deg))
+    }
+}
+
+// Converts a motor speed (or acceleration) in degrees per second to the robot's speed in mm/s
+fn motor_to_straight<Number>(motor_speed: Number, wheel_diameter: I32F32) -> I32F32
+where
+    Number: ToFixed,
+{
+    I32F32::from_num(motor_speed) * I32F32::PI * wheel_diameter / 360
+}
+
+// Converts a motor speed (or acceleration) in degrees per second to the robot's turn rate in degrees per second,
+// when the wheels turn in opposite directions
+fn motor_to_turn<Number>(motor_speed: Number, wheel_diameter: I32F32, axle_track: I32F32) -> I32F32
+where
+    Number: ToFixed,
+{
+    I32F32::from_num(motor_speed) * wheel_diameter / axle_track
+}
+
+fn positive<Number>(func: &str, value: Number) -> Ev3Result<I32F32>
+where
+    Number: ToFixed,
+{
+    let value = I32F32::from_num(value);
+    if value > 0 {
+        Ok(value)
+    } else {
+        Err(Ev3Error::InvalidValue {
+            func: func.into(),
+            value: value.to_string(),
+        })
+    }
+}
+
+// The distance to a wall in mm as the median of a few readings, since single readings can be spurious
+async fn wall_distance(sensor: &UltrasonicSensor) -> Ev3Result<Option<f64>> {
+    let mut filter = DistanceFilter::new(WALL_SAMPLES);
+    let mut timer = interval(Duration::from_millis(5));
+
+    while !filter.is_full() {
+        filter.sample(|| sensor.distance_mm())?;
+        timer.tick().await;
+    }
+
+    Ok(filter.median())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::parameters::{Direction, MotorPort, SensorPort};
+    use crate::sensor_driver::{SensorMode, SensorType};
+    use crate::sim::{SimDriveBase, TestSensor};
+
+    // checks that the pose is within 10 mm and 2 degrees of the true pose
+    fn assert_pose(pose: Pose, robot: &SimDriveBase) {
+        let (x, y, heading) = robot.pose();
+        let (pose_x, pose_y, pose_heading) = (
+            pose.x.to_num::<f64>(),
+            pose.y.to_num::<f64>(),
+            pose.heading.to_num::<f64>(),
+        );
+        assert!(
+            (pose_x - x).abs() < 10.0 && (pose_y - y).abs() < 10.0,
+            "position ({pose_x}, {pose_y}) instead of ({x}, {y})"
+        );
+        assert!(
+            (pose_heading - heading).abs() < 2.0,
+            "heading {pose_heading} instead of {heading}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pose_follows_the_robot() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0)
+            .with_gyro(SensorPort::In1);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let gyro = GyroSensor::new(SensorPort::In1)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?.with_gyro(&gyro)?;
+        drive.use_gyro(true)?;
+
+        drive.straight(300).await?;
+        assert_pose(drive.pose()?, &robot);
+
+        drive.turn(90).await?;
+        drive.curve(200, -90).await?;
+        drive.straight(-100).await?;
+        assert_pose(drive.pose()?, &robot);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drive_to_reaches_the_target() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        drive.reset_pose(100, 50, 0)?;
+        robot.set_pose(100.0, 50.0, 0.0);
+
+        drive.drive_to(400, -250).await?;
+        let (x, y, _) = robot.pose();
+        assert!(
+            (x - 400.0).abs() < 10.0 && (y + 250.0).abs() < 10.0,
+            "at ({x}, {y})"
+        );
+
+        drive.drive_to_pose(100, 50, 90).await?;
+        let (x, y, heading) = robot.pose();
+        assert!(
+            (x - 100.0).abs() < 10.0 && (y - 50.0).abs() < 10.0,
+            "at ({x}, {y})"
+        );
+        assert!((heading - 90.0).abs() < 2.0, "heading {heading}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drive_holds_the_heading_until_canceled() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+        drive.set_stop_action(Stop::Hold)?;
+
+        let stop_after = |seconds| async move {
+            tokio::time::sleep(Duration::from_secs(seconds)).await;
+            Ok(())
+        };
+
+        (drive.drive(200, 0), stop_after(3)).race().await?;
+
+        let (x, y, heading) = robot.pose();
+        assert!((x - 600.0).abs() < 30.0, "drove to {x}");
+        assert!(y.abs() < 5.0, "drifted to {y}");
+        assert!(heading.abs() < 1.0, "heading {heading}");
+
+        // the robot stopped when it was canceled
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let (stopped_x, _, _) = robot.pose();
+        assert!((stopped_x - x).abs() < 5.0, "kept driving to {stopped_x}");
+
+        // turning on the spot follows the turn rate, and holds the heading it reached
+        (drive.drive(0, 45), stop_after(2)).race().await?;
+        (drive.drive(0, 0), stop_after(1)).race().await?;
+
+        let (_, _, heading) = robot.pose();
+        assert!((heading - 90.0).abs() < 3.0, "heading {heading}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drive_until_stops_when_the_condition_holds() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+        drive.set_stop_action(Stop::Hold)?;
+
+        let distance = drive
+            .straight_until(wait_until(|| Ok(robot.pose().0 > 300.0)))
+            .await?;
+        let (x, _, _) = robot.pose();
+        assert!((x - 300.0).abs() < 10.0, "stopped at {x}");
+        assert!(
+            (distance.to_num::<f64>() - x).abs() < 5.0,
+            "measured {distance}"
+        );
+
+        let distance = drive
+            .drive_until(-100, 0, wait_until(|| Ok(robot.pose().0 < 100.0)))
+            .await?;
+        let (x, _, _) = robot.pose();
+        assert!((x - 100.0).abs() < 10.0, "stopped at {x}");
+        assert!(
+            (distance.to_num::<f64>() + 200.0).abs() < 10.0,
+            "measured {distance}"
+        );
+
+        // the robot stays where the condition stopped it
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!((robot.pose().0 - x).abs() < 2.0);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finds_the_axle_track() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0)
+            .with_gyro(SensorPort::In1);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let gyro = GyroSensor::new(SensorPort::In1)?;
+        let mut drive = DriveBase::new(&left, &right, 56.0, 110.0)?.with_gyro(&gyro)?;
+        drive.set_stop_action(Stop::Hold)?;
+
+        let axle_track = drive.find_calibrated_axle_track(20).await?;
+        assert!(
+            (axle_track.to_num::<f64>() - 120.0).abs() < 3.0,
+            "found {axle_track}"
+        );
+
+        // the gyro resets don't leave the next action with a stale heading target
+        let (x, y, heading) = robot.pose();
+        drive.straight(200).await?;
+
+        let (end_x, end_y, end_heading) = robot.pose();
+        let travelled = (end_x - x).hypot(end_y - y);
+        assert!(
+            (end_heading - heading).abs() < 2.0,
+            "turned from {heading} to {end_heading}"
+        );
+        assert!((travelled - 200.0).abs() < 10.0, "travelled {travelled}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gyro_drift_is_rejected() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0)
+            .with_gyro(SensorPort::In1)
+            .with_gyro_drift(0.5);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let gyro = GyroSensor::new(SensorPort::In1)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?.with_gyro(&gyro)?;
+        drive.use_gyro(true)?;
+        drive.set_stop_action(Stop::Hold)?;
+
+        let start = Instant::now();
+        for _ in 0..10 {
+            drive.straight(300).await?;
+            drive.straight(-300).await?;
+        }
+        let seconds = start.elapsed().as_secs_f64();
+
+        // the gyro drifted far, but the robot kept driving straight and knows its heading
+        let drift = f64::from(gyro.heading()?);
+        assert!(
+            drift > 0.4 * seconds,
+            "the gyro drifted {drift} in {seconds} s"
+        );
+        assert_pose(drive.pose()?, &robot);
+
+        let (_, y, heading) = robot.pose();
+        assert!(
+            heading.abs() < 2.0 && y.abs() < 10.0,
+            "at y {y}, heading {heading}"
+        );
+        let bias = drive.heading_estimator().bias().to_num::<f64>();
+        assert!((bias - 0.5).abs() < 0.1, "estimated a bias of {bias}");
+        Ok(())
+    }
+
+    // a robot with 56 mm wheels, which starts out measuring with 60 mm wheels
+    fn wheel_diameter_robot() -> Arc<SimDriveBase> {
+        Arc::new(SimDriveBase::new(
+            MotorPort::OutA,
+            MotorPort::OutD,
+            56.0,
+            120.0,
+        ))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finds_the_wheel_diameter_with_lines() -> Ev3Result<()> {
+        // 20 mm lines that start 100 mm and 600 mm in front of the sensor
+        let robot = wheel_diameter_robot();
+        let _sensor = TestSensor::new(
+            SensorPort::In1,
+            SensorType::Color,
+            SensorMode::ColorReflectedLight,
+            {
+                let robot = robot.clone();
+                move || {
+                    let x = robot.pose().0;
+                    if (100.0..120.0).contains(&x) || (600.0..620.0).contains(&x) {
+                        8
+                    } else {
+                        80
+                    }
+                }
+            },
+        );
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let sensor = ColorSensor::new(SensorPort::In1)?;
+        let mut drive = DriveBase::new(&left, &right, 60.0, 120.0)?;
+
+        let wheel_diameter = drive.find_wheel_diameter_with_lines(&sensor, 500).await?;
+        assert!(
+            (wheel_diameter.to_num::<f64>() - 56.0).abs() < 0.5,
+            "found {wheel_diameter}"
+        );
+        assert_eq!(drive.calibration().wheel_diameter, wheel_diameter);
+
+        // without lines, it gives up after twice the line spacing
+        robot.set_pose(0.0, 0.0, 0.0);
+        let result = drive.find_wheel_diameter_with_lines(&sensor, 40).await;
+        assert!(
+            matches!(result, Err(Ev3Error::InvalidValue { .. })),
+            "{result:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn finds_the_wheel_diameter_with_a_wall() -> Ev3Result<()> {
+        // a wall 1000 mm in front of the sensor
+        let robot = wheel_diameter_robot();
+        let _sensor = TestSensor::new(
+            SensorPort::In2,
+            SensorType::Ultrasonic,
+            SensorMode::UltrasonicDistanceCm,
+            {
+                let robot = robot.clone();
+                move || (1000.0 - robot.pose().0).round() as i32
+            },
+        );
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let sensor = UltrasonicSensor::new(SensorPort::In2)?;
+        let mut drive = DriveBase::new(&left, &right, 60.0, 120.0)?;
+
+        let wheel_diameter = drive.find_wheel_diameter_with_wall(&sensor, 500).await?;
+        assert!(
+            (wheel_diameter.to_num::<f64>() - 56.0).abs() < 0.5,
+            "found {wheel_diameter}"
+        );
+        assert_eq!(drive.calibration().wheel_diameter, wheel_diameter);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn obstacle_guard_stops_before_an_obstacle() -> Ev3Result<()> {
+        // a wall 600 mm in front of the robot
+        let robot = Arc::new(SimDriveBase::new(
+            MotorPort::OutA,
+            MotorPort::OutD,
+            56.0,
+            120.0,
+        ));
+        let _sensor = TestSensor::new(
+            SensorPort::In2,
+            SensorType::Ultrasonic,
+            SensorMode::UltrasonicDistanceCm,
+            {
+                let robot = robot.clone();
+                move || (600.0 - robot.pose().0).round() as i32
+            },
+        );
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let sensor = UltrasonicSensor::new(SensorPort::In2)?;
+        let drive =
+            DriveBase::new(&left, &right, 56.0, 120.0)?.with_ultrasonic_guard(&sensor, 100, 300)?;
+        drive.set_stop_action(Stop::Hold)?;
+
+        let result = drive.straight(1000).await;
+        assert!(
+            matches!(result, Err(Ev3Error::Blocked { distance }) if distance < 100),
+            "{result:?}"
+        );
+
+        let (x, _, _) = robot.pose();
+        assert!((450.0..520.0).contains(&x), "stopped at {x}");
+
+        // driving away from the obstacle isn't guarded
+        drive.straight(-200).await?;
+        let (x, _, _) = robot.pose();
+        assert!(x < 320.0, "only backed up to {x}");
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_stalled_wheel_stops_the_robot() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        robot.right_motor().set_load(1.0);
+
+        let start = Instant::now();
+        let result = drive.straight(500).await;
+        assert!(
+            matches!(result, Err(Ev3Error::Stalled { side: Side::Right })),
+            "{result:?}"
+        );
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "took {:?}",
+            start.elapsed()
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn actions_time_out_after_their_planned_motion() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?
+            .with_stall_detection(None)
+            .with_timeout(Some(Duration::from_secs(1)));
+
+        robot.left_motor().set_load(1.0);
+
+        let start = Instant::now();
+        let result = drive.straight(100).await;
+        assert!(
+            matches!(result, Err(Ev3Error::TimedOut { .. })),
+            "{result:?}"
+        );
+
+        // 100 mm take less than a second at the default speeds
+        let elapsed = start.elapsed();
+        assert!(
+            (Duration::from_secs(1)..Duration::from_secs(3)).contains(&elapsed),
+            "took {elapsed:?}"
+        );
+
+        // the next action starts from where the robot stopped
+        robot.left_motor().set_load(0.0);
+        let (x, y, _) = robot.pose();
+        drive.straight(100).await?;
+
+        let (end_x, end_y, _) = robot.pose();
+        let travelled = (end_x - x).hypot(end_y - y);
+        assert!((travelled - 100.0).abs() < 10.0, "travelled {travelled}");
+        Ok(())
     }
 }
@@ -0,0 +1,354 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+
+// how unsure the bias is before anything is measured, in (degrees per second)²
+const INITIAL_BIAS_VARIANCE: f64 = 1.0;
+
+/// Estimates the heading of a robot from a gyro and the wheel encoders with a Kalman filter.
+///
+/// The gyro is accurate over short times, but drifts away slowly because of its bias,
+/// while the encoders don't drift while the robot stands still, but lose track of the heading when the wheels slip.
+/// The filter follows the gyro, estimates its bias whenever the encoders can be trusted,
+/// and takes the bias back out of the heading.
+///
+/// Slipping that turns the robot while the encoders don't measure any turning, like one wheel slipping while driving straight,
+/// looks the same as gyro bias, so the bias is estimated best while the robot stands still now and then.
+///
+/// How much the filter trusts each source is set with the noise parameters.
+/// The defaults suit the EV3 gyro sensor and rubber tires on a smooth surface.
+///
+/// The heading is in degrees and clockwise, like the heading of a `DriveBase`, which uses this filter when `use_gyro` is enabled.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use std::time::Duration;
+/// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+/// use ev3dev_rs::pupdevices::{GyroSensor, Motor};
+/// use ev3dev_rs::robotics::HeadingEstimator;
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+/// let gyro = GyroSensor::new(SensorPort::In1)?;
+///
+/// let estimator = HeadingEstimator::new().with_wheel_slip(0.1);
+///
+/// // 56 mm wheels, 120 mm apart
+/// let encoder_heading = || -> ev3dev_rs::Ev3Result<f64> {
+///     Ok(f64::from(left.angle()? - right.angle()?) * 56.0 / (2.0 * 120.0))
+/// };
+///
+/// let mut timer = tokio::time::interval(Duration::from_millis(10));
+/// loop {
+///     timer.tick().await;
+///     let heading = estimator.update(f64::from(gyro.heading()?), encoder_heading()?, Duration::from_millis(10));
+///     println!("heading {heading}, gyro bias {}", estimator.bias());
+/// }
+/// # }
+/// ```
+pub struct HeadingEstimator {
+    gyro_noise: Cell<f64>,
+    bias_drift: Cell<f64>,
+    encoder_noise: Cell<f64>,
+    wheel_slip: Cell<f64>,
+    // the heading, the gyro bias and how far the encoder heading is off because of slipping
+    state: Cell<[f64; 3]>,
+    covariance: Cell<[[f64; 3]; 3]>,
+    // the last gyro and encoder headings
+    prev_readings: Cell<Option<(f64, f64)>>,
+}
+
+impl Default for HeadingEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HeadingEstimator {
+    /// Creates a new `HeadingEstimator`.
+    ///
+    /// The defaults are a gyro noise of 0.05, a bias drift of 0.00001, an encoder noise of 0.25 and a wheel slip of 0.05.
+    pub fn new() -> Self {
+        Self {
+            gyro_noise: Cell::new(0.05),
+            bias_drift: Cell::new(0.00001),
+            encoder_noise: Cell::new(0.25),
+            wheel_slip: Cell::new(0.05),
+            state: Cell::new([0.0; 3]),
+            covariance: Cell::new([
+                [0.0, 0.0, 0.0],
+                [0.0, INITIAL_BIAS_VARIANCE, 0.0],
+                [0.0, 0.0, 0.0],
+            ]),
+            prev_readings: Cell::new(None),
+        }
+    }
+
+    /// Sets how far the gyro heading wanders off besides its bias, in degrees² per second.
+    ///
+    /// A higher value trusts the encoders more.
+    pub fn with_gyro_noise<Number>(self, gyro_noise: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.gyro_noise.set(non_negative(gyro_noise));
+        self
+    }
+
+    /// Sets how fast the gyro bias changes, in (degrees per second)² per second.
+    ///
+    /// A higher value follows changes in the bias faster, but makes the estimate noisier.
+    pub fn with_bias_drift<Number>(self, bias_drift: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.bias_drift.set(non_negative(bias_drift));
+        self
+    }
+
+    /// Sets how noisy the encoder heading is, in degrees².
+    pub fn with_encoder_noise<Number>(self, encoder_noise: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.encoder_noise.set(non_negative(encoder_noise));
+        self
+    }
+
+    /// Sets how much of the turning measured by the encoders might be lost to slipping, as a fraction (0 to 1).
+    ///
+    /// A higher value trusts the encoders less while turning.
+    pub fn with_wheel_slip<Number>(self, wheel_slip: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.wheel_slip.set(non_negative(wheel_slip));
+        self
+    }
+
+    /// Gets the estimated heading in degrees.
+    pub fn heading(&self) -> I32F32 {
+        I32F32::from_num(self.state.get()[0])
+    }
+
+    /// Gets the estimated gyro bias in degrees per second,
+    /// which is how fast the gyro heading drifts while the robot stands still.
+    pub fn bias(&self) -> I32F32 {
+        I32F32::from_num(self.state.get()[1])
+    }
+
+    /// Predicts the heading in degrees from the gyro heading and the time since the last update,
+    /// without changing the estimate.
+    ///
+    /// This is for reading the heading between updates, since the same readings shouldn't be fused twice.
+    /// Before the first update, this is the gyro heading.
+    pub fn predict<Number>(&self, gyro_heading: Number, elapsed: Duration) -> I32F32
+    where
+        Number: ToFixed,
+    {
+        let gyro: f64 = I32F32::from_num(gyro_heading).to_num();
+
+        let Some((prev_gyro, _)) = self.prev_readings.get() else {
+            return I32F32::from_num(gyro);
+        };
+
+        let [heading, bias, _] = self.state.get();
+        I32F32::from_num(heading + gyro - prev_gyro - bias * elapsed.as_secs_f64())
+    }
+
+    /// Starts over from the next readings, which is needed when the gyro or the encoders are reset.
+    ///
+    /// The estimated heading restarts at the next gyro heading, while the estimated bias is kept.
+    pub fn reset(&self) {
+        self.prev_readings.set(None);
+    }
+
+    /// Updates the estimate with the headings in degrees from the gyro and the encoders,
+    /// and the time since the last update, and returns the estimated heading.
+    ///
+    /// The headings only have to change together, so they can start at different values.
+    pub fn update<Number>(
+        &self,
+        gyro_heading: Number,
+        encoder_heading: Number,
+        dt: Duration,
+    ) -> I32F32
+    where
+        Number: ToFixed,
+    {
+        let gyro: f64 = I32F32::from_num(gyro_heading).to_num();
+        let encoders: f64 = I32F32::from_num(encoder_heading).to_num();
+        let dt = dt.as_secs_f64();
+
+        let Some((prev_gyro, prev_encoders)) = self.prev_readings.get() else {
+            self.prev_readings.set(Some((gyro, encoders)));
+
+            // start at the gyro heading, where the encoders are off by however far they are from it
+            let mut state = self.state.get();
+            state[0] = gyro;
+            state[2] = encoders - gyro;
+            self.state.set(state);
+
+            let bias_variance = self.covariance.get()[1][1];
+            self.covariance
+                .set([[0.0, 0.0, 0.0], [0.0, bias_variance, 0.0], [0.0, 0.0, 0.0]]);
+            return self.heading();
+        };
+
+        // the same readings shouldn't count twice, so updates without any time passing are skipped
+        if dt <= 0.0 {
+            return self.heading();
+        }
+        self.prev_readings.set(Some((gyro, encoders)));
+
+        let [mut heading, bias, slip_offset] = self.state.get();
+        let mut p = self.covariance.get();
+
+        // predict: the heading turns by what the gyro measured, minus the bias
+        heading += gyro - prev_gyro - bias * dt;
+
+        // P = F P Fᵀ + Q, where F only adds -dt times the bias to the heading
+        let bias_row = p[1];
+        for (value, bias_value) in p[0].iter_mut().zip(bias_row) {
+            *value -= dt * bias_value;
+        }
+        for row in p.iter_mut() {
+            row[0] -= dt * row[1];
+        }
+
+        // the slip offset can only change while the encoders measure turning,
+        // and slipping is the same throughout a turn, so its deviation grows with the turn instead of its variance
+        let slip = self.wheel_slip.get() * (encoders - prev_encoders).abs();
+        p[0][0] += self.gyro_noise.get() * dt;
+        p[1][1] += self.bias_drift.get() * dt;
+        p[2][2] = (p[2][2].sqrt() + slip).powi(2);
+
+        // update: the encoders measure the heading plus the slip offset
+        let innovation = encoders - (heading + slip_offset);
+        let variance = p[0][0] + p[0][2] + p[2][0] + p[2][2] + self.encoder_noise.get();
+        if variance > 0.0 {
+            let gain = [
+                (p[0][0] + p[0][2]) / variance,
+                (p[1][0] + p[1][2]) / variance,
+                (p[2][0] + p[2][2]) / variance,
+            ];
+
+            let h_p = [p[0][0] + p[2][0], p[0][1] + p[2][1], p[0][2] + p[2][2]];
+            for (i, row) in p.iter_mut().enumerate() {
+                for (j, value) in row.iter_mut().enumerate() {
+                    *value -= gain[i] * h_p[j];
+                }
+            }
+
+            self.state.set([
+                heading + gain[0] * innovation,
+                bias + gain[1] * innovation,
+                slip_offset + gain[2] * innovation,
+            ]);
+        } else {
+            self.state.set([heading, bias, slip_offset]);
+        }
+
+        self.covariance.set(p);
+        self.heading()
+    }
+}
+
+fn non_negative<Number>(value: Number) -> f64
+where
+    Number: ToFixed,
+{
+    I32F32::from_num(value).to_num::<f64>().abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(10);
+
+    // stands still for the given time with a gyro that drifts at the given bias, starting at the given time
+    fn stand_still(estimator: &HeadingEstimator, bias: f64, from: f64, seconds: f64) -> f64 {
+        let steps = (seconds / DT.as_secs_f64()).round() as u32;
+        let mut t = from;
+        for _ in 0..steps {
+            t += DT.as_secs_f64();
+            estimator.update(bias * t, 0.0, DT);
+        }
+        t
+    }
+
+    fn assert_close(value: I32F32, expected: f64, tolerance: f64) {
+        let value = value.to_num::<f64>();
+        assert!(
+            (value - expected).abs() < tolerance,
+            "{value} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn bias_converges() {
+        let estimator = HeadingEstimator::new();
+        estimator.update(0.0, 0.0, Duration::ZERO);
+
+        stand_still(&estimator, 0.5, 0.0, 60.0);
+
+        assert_close(estimator.bias(), 0.5, 0.02);
+        // the gyro drifted by 30 degrees, but the robot didn't turn
+        assert_close(estimator.heading(), 0.0, 1.0);
+    }
+
+    #[test]
+    fn follows_the_gyro_while_turning() {
+        let estimator = HeadingEstimator::new().with_wheel_slip(0.1);
+        estimator.update(0.0, 0.0, Duration::ZERO);
+
+        // turning at 90 degrees per second, with the encoders losing 5% to slipping
+        for step in 1..=100 {
+            let heading = 0.9 * f64::from(step);
+            estimator.update(heading, 0.95 * heading, DT);
+        }
+
+        assert_close(estimator.heading(), 90.0, 2.0);
+    }
+
+    #[test]
+    fn reset() {
+        let estimator = HeadingEstimator::new();
+        estimator.update(0.0, 0.0, Duration::ZERO);
+        stand_still(&estimator, 0.5, 0.0, 60.0);
+        let bias = estimator.bias();
+
+        // the gyro was reset to zero, and the encoders to 45
+        estimator.reset();
+        assert_eq!(estimator.update(0.0, 45.0, DT), I32F32::ZERO);
+        assert_eq!(estimator.bias(), bias);
+
+        // the drift is still taken out after the reset
+        let mut gyro = 0.0;
+        for _ in 0..100 {
+            gyro += 0.5 * DT.as_secs_f64();
+            estimator.update(gyro, 45.0, DT);
+        }
+        assert_close(estimator.heading(), 0.0, 0.1);
+    }
+
+    #[test]
+    fn predict_doesnt_change_the_estimate() {
+        let estimator = HeadingEstimator::new();
+        assert_eq!(estimator.predict(12, Duration::ZERO), I32F32::from_num(12));
+
+        estimator.update(0.0, 0.0, Duration::ZERO);
+        let t = stand_still(&estimator, 0.5, 0.0, 60.0);
+        let (heading, bias) = (estimator.heading(), estimator.bias());
+
+        // a second later, the gyro drifted another half degree and turned 10 degrees
+        let predicted = estimator.predict(0.5 * (t + 1.0) + 10.0, Duration::from_secs(1));
+        assert_close(predicted, heading.to_num::<f64>() + 10.0, 0.05);
+        assert_eq!((estimator.heading(), estimator.bias()), (heading, bias));
+    }
+}
@@ -0,0 +1,353 @@
+use std::future::Future;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+use scopeguard::defer;
+use tokio::time::{Instant, interval};
+
+use crate::Race;
+use crate::error::{Ev3Error, Ev3Result};
+use crate::parameters::{Side, Stop};
+use crate::pupdevices::ColorSensor;
+use crate::robotics::{DriveBase, Pid};
+
+enum Sensors<'c> {
+    // follows one edge of the line
+    Single(&'c ColorSensor, Side),
+    // straddles the line, with one sensor on each side
+    Dual(&'c ColorSensor, &'c ColorSensor),
+}
+
+/// Steers a `DriveBase` along a line with one or two `ColorSensor`s in reflection mode.
+///
+/// With one sensor, the robot follows one edge of the line,
+/// steering to keep the reflection at the target reflection.
+/// The target is usually halfway between the reflection of the line and of the background.
+///
+/// With two sensors, one on each side of the line, the robot steers to keep both reflections equal.
+/// A junction is detected when both sensors see the line at the same time.
+///
+/// The turn rate is calculated with a `Pid`, see `with_pid_settings`.
+/// The motor ramps are turned off while following the line, since they would delay the steering.
+/// Once following ends, the next drive action starts from where the robot stopped.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort, Side};
+/// use ev3dev_rs::pupdevices::{ColorSensor, Motor};
+/// use ev3dev_rs::robotics::{DriveBase, LineFollower};
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+///
+/// let left_sensor = ColorSensor::new(SensorPort::In1)?;
+/// let right_sensor = ColorSensor::new(SensorPort::In4)?;
+///
+/// // follow the left edge of the line for 500 mm
+/// let edge = LineFollower::new(&drive, &left_sensor, Side::Left)
+///     .with_target_reflection(45)
+///     .with_speed(200);
+/// edge.follow_distance(500).await?;
+///
+/// // then straddle the line until the next junction
+/// let line = LineFollower::new_dual(&drive, &left_sensor, &right_sensor).with_black_threshold(20);
+/// line.follow_to_junction().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct LineFollower<'a, 'b, 'c> {
+    drive: &'b DriveBase<'a>,
+    sensors: Sensors<'c>,
+    pid: Pid,
+    target_reflection: I32F32,
+    black_threshold: u8,
+    speed: f64,
+    stop_action: Stop,
+}
+
+impl<'a, 'b, 'c> LineFollower<'a, 'b, 'c> {
+    /// Creates a new `LineFollower` that follows the given edge of the line with one sensor.
+    ///
+    /// `Side::Left` follows the left edge of the line, so the line is to the right of the sensor.
+    ///
+    /// The defaults are a target reflection of 50, a black threshold of 15,
+    /// a speed of 150 mm/s and a stop action of `Stop::Hold`.
+    pub fn new(drive: &'b DriveBase<'a>, sensor: &'c ColorSensor, edge: Side) -> Self {
+        Self::with_sensors(drive, Sensors::Single(sensor, edge))
+    }
+
+    /// Creates a new `LineFollower` that straddles the line with two sensors.
+    ///
+    /// See `new` for the defaults.
+    pub fn new_dual(
+        drive: &'b DriveBase<'a>,
+        left_sensor: &'c ColorSensor,
+        right_sensor: &'c ColorSensor,
+    ) -> Self {
+        Self::with_sensors(drive, Sensors::Dual(left_sensor, right_sensor))
+    }
+
+    fn with_sensors(drive: &'b DriveBase<'a>, sensors: Sensors<'c>) -> Self {
+        Self {
+            drive,
+            sensors,
+            pid: Pid::new(10, 0, 0),
+            target_reflection: I32F32::from_num(50),
+            black_threshold: 15,
+            speed: 150.0,
+            stop_action: Stop::Hold,
+        }
+    }
+
+    /// Sets the reflection that a single sensor keeps to, as a percentage (0 to 100).
+    ///
+    /// This is ignored when following with two sensors.
+    pub fn with_target_reflection<Number>(mut self, target_reflection: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.target_reflection = I32F32::from_num(target_reflection);
+        self
+    }
+
+    /// Sets the reflection below which a sensor sees the line, as a percentage (0 to 100).
+    ///
+    /// This is used for detecting junctions.
+    pub fn with_black_threshold(mut self, black_threshold: u8) -> Self {
+        self.black_threshold = black_threshold;
+        self
+    }
+
+    /// Sets the driving speed in mm/s.
+    pub fn with_speed<Number>(mut self, speed: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.speed = I32F32::from_num(speed).to_num();
+        self
+    }
+
+    /// Sets the PID settings for steering.
+    ///
+    /// The error is the difference in reflection, and the output is the turn rate in degrees per second,
+    /// limited to the turn rate of the `DriveBase`. See `Pid` for the gains and `Pid::set_integral_limits`
+    /// for the integral deadzone and rate.
+    ///
+    /// The default is `(10, 0, 0, 0, 0)`.
+    pub fn with_pid_settings<Number>(
+        self,
+        kp: Number,
+        ki: Number,
+        kd: Number,
+        integral_deadzone: Number,
+        integral_rate: Number,
+    ) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.pid.settings(kp, ki, kd);
+        self.pid
+            .set_integral_limits(integral_deadzone, integral_rate);
+        self
+    }
+
+    /// Sets how the robot stops when it is done following the line.
+    ///
+    /// This doesn't change the stop action of the `DriveBase`.
+    pub fn with_stop_action(mut self, action: Stop) -> Self {
+        self.stop_action = action;
+        self
+    }
+
+    /// Follows the line for the given distance in mm, and returns the distance travelled in mm.
+    pub async fn follow_distance<Number>(&self, distance: Number) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+    {
+        let distance = I32F32::from_num(distance).abs();
+        let start = self.drive.distance()?;
+
+        self.follow_while(|| Ok((self.drive.distance()? - start).abs() < distance))
+            .await
+    }
+
+    /// Follows the line until both sensors see the line at the same time,
+    /// and returns the distance travelled in mm.
+    ///
+    /// Returns `InvalidValue` if the `LineFollower` only has one sensor.
+    pub async fn follow_to_junction(&self) -> Ev3Result<I32F32> {
+        let Sensors::Dual(left, right) = self.sensors else {
+            return Err(Ev3Error::InvalidValue {
+                func: "LineFollower::follow_to_junction".into(),
+                value: "one sensor".into(),
+            });
+        };
+
+        self.follow_while(|| {
+            Ok(left.reflection()? >= self.black_threshold
+                || right.reflection()? >= self.black_threshold)
+        })
+        .await
+    }
+
+    /// Follows the line until the condition completes, and returns the distance travelled in mm.
+    ///
+    /// See `DriveBase::straight_until`.
+    pub async fn follow_until<C>(&self, condition: C) -> Ev3Result<I32F32>
+    where
+        C: Future<Output = Ev3Result<()>>,
+    {
+        let start = self.drive.distance()?;
+
+        // following stops the robot when it is canceled by the condition
+        (self.follow_while(|| Ok(true)), async {
+            condition.await?;
+            Ok(I32F32::ZERO)
+        })
+            .race()
+            .await?;
+
+        Ok(self.drive.distance()? - start)
+    }
+
+    // Follows the line as long as the condition returns true, and returns the distance travelled
+    async fn follow_while<F>(&self, mut condition: F) -> Ev3Result<I32F32>
+    where
+        F: FnMut() -> Ev3Result<bool>,
+    {
+        defer! {
+            _ = self.stop();
+            _ = self.drive.enable_ramps(true);
+            _ = self.drive.sync_targets();
+        }
+
+        self.drive.enable_ramps(false)?;
+        self.pid.reset();
+
+        let start = self.drive.distance()?;
+        let turn_rate = self.drive.max_turn_rate();
+        self.pid.set_output_limits(-turn_rate, turn_rate)?;
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        let mut last_tick = Instant::now();
+
+        while condition()? {
+            let now = Instant::now();
+            self.drive.tick(now)?;
+
+            // how far the robot is to the right of the line, in reflection,
+            // which the PID drives to zero by turning left for positive values
+            let offset = match self.sensors {
+                Sensors::Single(sensor, Side::Left) => {
+                    self.target_reflection - I32F32::from_num(sensor.reflection()?)
+                }
+                Sensors::Single(sensor, Side::Right) => {
+                    I32F32::from_num(sensor.reflection()?) - self.target_reflection
+                }
+                Sensors::Dual(left, right) => {
+                    I32F32::from_num(right.reflection()?) - I32F32::from_num(left.reflection()?)
+                }
+            };
+
+            let turn = self.pid.update(offset, now - last_tick);
+            last_tick = now;
+
+            self.drive.run_at(self.speed, turn.to_num())?;
+
+            timer.tick().await;
+        }
+
+        Ok(self.drive.distance()? - start)
+    }
+
+    fn stop(&self) -> Ev3Result<()> {
+        self.drive.stop_with(self.stop_action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::parameters::{Direction, MotorPort, SensorPort};
+    use crate::pupdevices::Motor;
+    use crate::sensor_driver::{SensorMode, SensorType};
+    use crate::sim::{SimDriveBase, TestSensor};
+
+    // how far in front of the wheels the sensor is (mm)
+    const SENSOR_OFFSET: f64 = 60.0;
+
+    // The reflection under the sensor, with a 20 mm wide line along the x axis.
+    // The light spot of the sensor is about 10 mm wide, so the reflection changes gradually over the edges.
+    fn reflection(robot: &SimDriveBase) -> i32 {
+        let (_, y, heading) = robot.pose();
+        let sensor_y = y + SENSOR_OFFSET * heading.to_radians().sin();
+        let covered = ((15.0 - sensor_y.abs()) / 10.0).clamp(0.0, 1.0);
+        (80.0 - 70.0 * covered).round() as i32
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follows_the_edge_and_syncs_the_targets() -> Ev3Result<()> {
+        let robot = Arc::new(SimDriveBase::new(
+            MotorPort::OutA,
+            MotorPort::OutD,
+            56.0,
+            120.0,
+        ));
+        let _sensor = TestSensor::new(
+            SensorPort::In1,
+            SensorType::Color,
+            SensorMode::ColorReflectedLight,
+            {
+                let robot = robot.clone();
+                move || reflection(&robot)
+            },
+        );
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+        let sensor = ColorSensor::new(SensorPort::In1)?;
+
+        // start left of the line, facing across it
+        drive.turn(20).await?;
+        robot.set_pose(0.0, -60.0, robot.pose().2);
+
+        let travelled = LineFollower::new(&drive, &sensor, Side::Left)
+            .with_target_reflection(45)
+            .follow_distance(600)
+            .await?;
+        assert!(
+            (travelled - I32F32::from_num(600)).abs() < 10,
+            "travelled {travelled}"
+        );
+
+        // the sensor ends up on the left edge of the line, with the robot along the line
+        let (x, y, heading) = robot.pose();
+        let sensor_y = y + SENSOR_OFFSET * heading.to_radians().sin();
+        assert!((sensor_y + 10.0).abs() < 5.0, "sensor at {sensor_y}");
+        assert!(heading.abs() < 5.0, "heading {heading}");
+
+        drive.straight(100).await?;
+
+        let (end_x, _, end_heading) = robot.pose();
+        assert!(
+            (end_heading - heading).abs() < 2.0,
+            "turned from {heading} to {end_heading}"
+        );
+        assert!(
+            (end_x - x - 100.0).abs() < 10.0,
+            "drove from {x} to {end_x}"
+        );
+        Ok(())
+    }
+}
@@ -1,5 +1,25 @@
+mod calibration;
+mod distance_filter;
 mod drive_base;
 mod gyro_controller;
+mod heading_estimator;
+mod line_follower;
+mod motion_profile;
+mod obstacle_guard;
+mod odometry;
+mod path_follower;
+mod pid;
+mod relay_tuner;
+mod wall_follower;
 
+pub use calibration::Calibration;
 pub use drive_base::DriveBase;
 pub use gyro_controller::GyroController;
+pub use heading_estimator::HeadingEstimator;
+pub use line_follower::LineFollower;
+pub use motion_profile::{MotionProfile, ProfileShape};
+pub use odometry::Pose;
+pub use path_follower::PathFollower;
+pub use pid::{AntiWindup, Pid, PidGains};
+pub use relay_tuner::{RelayResult, RelayTuner, TuningRule};
+pub use wall_follower::WallFollower;
@@ -0,0 +1,318 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+
+use crate::error::{Ev3Error, Ev3Result};
+
+/// The shape of the speed changes in a `MotionProfile`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProfileShape {
+    /// Constant acceleration, which gives a trapezoid shaped speed over time.
+    Trapezoidal,
+    /// Acceleration that smoothly rises and falls, which limits the jerk.
+    ///
+    /// The peak acceleration is the same as with `Trapezoidal`, so speeding up takes about 1.57 times as long.
+    SCurve,
+}
+
+impl ProfileShape {
+    // how much longer a speed change takes than with constant acceleration
+    fn stretch(&self) -> f64 {
+        match self {
+            ProfileShape::Trapezoidal => 1.0,
+            ProfileShape::SCurve => PI / 2.0,
+        }
+    }
+
+    // the distance covered after `t` seconds of a speed change from 0 to `peak_speed` that takes `duration` seconds
+    fn ramp_distance(&self, t: f64, duration: f64, peak_speed: f64) -> f64 {
+        match self {
+            ProfileShape::Trapezoidal => peak_speed * t * t / (2.0 * duration),
+            ProfileShape::SCurve => {
+                peak_speed / 2.0 * (t - duration / PI * (PI * t / duration).sin())
+            }
+        }
+    }
+
+    // the speed after `t` seconds of a speed change from 0 to `peak_speed` that takes `duration` seconds
+    fn ramp_speed(&self, t: f64, duration: f64, peak_speed: f64) -> f64 {
+        match self {
+            ProfileShape::Trapezoidal => peak_speed * t / duration,
+            ProfileShape::SCurve => peak_speed / 2.0 * (1.0 - (PI * t / duration).cos()),
+        }
+    }
+}
+
+/// A planned move that speeds up, cruises, and slows down to cover a distance.
+///
+/// The units are up to you, as long as they match,
+/// like mm, mm/s and mm/s² or degrees, degrees per second and degrees per second².
+///
+/// If the distance is too short to reach the max speed, the profile speeds up and then
+/// immediately slows down again.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use ev3dev_rs::robotics::{MotionProfile, ProfileShape};
+///
+/// // 500 mm at up to 200 mm/s, speeding up and slowing down at 400 mm/s²
+/// let profile = MotionProfile::new(500, 200, 400, 400, ProfileShape::Trapezoidal)?;
+///
+/// assert_eq!(profile.duration(), Duration::from_millis(3000));
+/// assert_eq!(profile.speed(Duration::from_millis(1500)), 200);
+/// assert_eq!(profile.position(profile.duration()), 500);
+/// # Ok::<(), ev3dev_rs::Ev3Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MotionProfile {
+    shape: ProfileShape,
+    // 1 or -1
+    direction: f64,
+    distance: f64,
+    peak_speed: f64,
+    accel_time: f64,
+    cruise_time: f64,
+    decel_time: f64,
+}
+
+impl MotionProfile {
+    /// Plans a move over the given distance, which can be negative.
+    ///
+    /// The max speed, acceleration and deceleration must be positive, otherwise `InvalidValue` is returned.
+    pub fn new<Number>(
+        distance: Number,
+        max_speed: Number,
+        acceleration: Number,
+        deceleration: Number,
+        shape: ProfileShape,
+    ) -> Ev3Result<Self>
+    where
+        Number: ToFixed,
+    {
+        let distance: f64 = I32F32::from_num(distance).to_num();
+        let max_speed: f64 = I32F32::from_num(max_speed).to_num();
+        let acceleration: f64 = I32F32::from_num(acceleration).to_num();
+        let deceleration: f64 = I32F32::from_num(deceleration).to_num();
+
+        for value in [max_speed, acceleration, deceleration] {
+            if value <= 0.0 {
+                return Err(Ev3Error::InvalidValue {
+                    func: "MotionProfile::new".into(),
+                    value: value.to_string(),
+                });
+            }
+        }
+
+        let direction = if distance < 0.0 { -1.0 } else { 1.0 };
+        let distance = distance.abs();
+        let k = shape.stretch();
+
+        // a speed change from 0 to v takes k * v / a seconds and covers k * v² / 2a
+        let ramps_distance =
+            |speed: f64| k * speed * speed / 2.0 * (1.0 / acceleration + 1.0 / deceleration);
+
+        let peak_speed = if ramps_distance(max_speed) > distance {
+            (2.0 * distance / (k * (1.0 / acceleration + 1.0 / deceleration))).sqrt()
+        } else {
+            max_speed
+        };
+
+        let cruise_time = if peak_speed > 0.0 {
+            (distance - ramps_distance(peak_speed)).max(0.0) / peak_speed
+        } else {
+            0.0
+        };
+
+        Ok(Self {
+            shape,
+            direction,
+            distance,
+            peak_speed,
+            accel_time: k * peak_speed / acceleration,
+            cruise_time,
+            decel_time: k * peak_speed / deceleration,
+        })
+    }
+
+    /// Gets how long the move takes.
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.total_time())
+    }
+
+    /// Slows the profile down so that it takes the given duration, keeping its shape.
+    ///
+    /// This is useful for making two moves finish at the same time.
+    /// Durations shorter than the current duration are ignored.
+    pub fn stretch_to(&mut self, duration: Duration) {
+        let total = self.total_time();
+        let duration = duration.as_secs_f64();
+        if total <= 0.0 || duration <= total {
+            return;
+        }
+
+        let factor = duration / total;
+        self.accel_time *= factor;
+        self.cruise_time *= factor;
+        self.decel_time *= factor;
+        self.peak_speed /= factor;
+    }
+
+    /// Gets the planned position at the given time since the start of the move.
+    pub fn position(&self, elapsed: Duration) -> I32F32 {
+        I32F32::from_num(self.position_f64(elapsed.as_secs_f64()))
+    }
+
+    /// Gets the planned speed at the given time since the start of the move.
+    pub fn speed(&self, elapsed: Duration) -> I32F32 {
+        I32F32::from_num(self.speed_f64(elapsed.as_secs_f64()))
+    }
+
+    fn total_time(&self) -> f64 {
+        self.accel_time + self.cruise_time + self.decel_time
+    }
+
+    pub(crate) fn position_f64(&self, t: f64) -> f64 {
+        let accel_distance = self.peak_speed * self.accel_time / 2.0;
+
+        let position = if t <= 0.0 {
+            0.0
+        } else if t < self.accel_time {
+            self.shape
+                .ramp_distance(t, self.accel_time, self.peak_speed)
+        } else if t < self.accel_time + self.cruise_time {
+            accel_distance + self.peak_speed * (t - self.accel_time)
+        } else if t < self.total_time() {
+            // slowing down is speeding up backwards in time from the end
+            let remaining = self.total_time() - t;
+            self.distance
+                - self
+                    .shape
+                    .ramp_distance(remaining, self.decel_time, self.peak_speed)
+        } else {
+            self.distance
+        };
+
+        self.direction * position
+    }
+
+    pub(crate) fn speed_f64(&self, t: f64) -> f64 {
+        let speed = if t <= 0.0 || t >= self.total_time() {
+            0.0
+        } else if t < self.accel_time {
+            self.shape.ramp_speed(t, self.accel_time, self.peak_speed)
+        } else if t < self.accel_time + self.cruise_time {
+            self.peak_speed
+        } else {
+            let remaining = self.total_time() - t;
+            self.shape
+                .ramp_speed(remaining, self.decel_time, self.peak_speed)
+        };
+
+        self.direction * speed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 0.01
+    }
+
+    #[test]
+    fn trapezoidal() -> Ev3Result<()> {
+        let profile = MotionProfile::new(500, 200, 400, 200, ProfileShape::Trapezoidal)?;
+
+        // 0.5 s and 50 mm to speed up, 1 s and 100 mm to slow down, and 350 mm at 200 mm/s in between
+        assert!(close(profile.duration().as_secs_f64(), 3.25));
+        assert!(close(profile.speed_f64(0.25), 100.0));
+        assert!(close(profile.position_f64(0.5), 50.0));
+        assert!(close(profile.speed_f64(1.0), 200.0));
+        assert!(close(profile.position_f64(2.25), 400.0));
+        assert!(close(profile.speed_f64(2.75), 100.0));
+        Ok(())
+    }
+
+    #[test]
+    fn triangular_when_the_distance_is_too_short() -> Ev3Result<()> {
+        let profile = MotionProfile::new(50, 200, 400, 400, ProfileShape::Trapezoidal)?;
+
+        // the peak speed is where speeding up and slowing down cover the distance together
+        let peak_speed = 20000f64.sqrt();
+        let half = peak_speed / 400.0;
+        assert!(close(profile.duration().as_secs_f64(), 2.0 * half));
+        assert!(close(profile.speed_f64(half), peak_speed));
+        assert!(close(profile.position_f64(half), 25.0));
+        assert!(
+            (0..=100)
+                .map(|i| profile.speed_f64(f64::from(i) * 0.01))
+                .all(|speed| speed <= peak_speed + 0.01)
+        );
+        assert_eq!(profile.position(profile.duration()), 50);
+        Ok(())
+    }
+
+    #[test]
+    fn negative_distance() -> Ev3Result<()> {
+        let forward = MotionProfile::new(500, 200, 400, 400, ProfileShape::SCurve)?;
+        let backward = MotionProfile::new(-500, 200, 400, 400, ProfileShape::SCurve)?;
+
+        assert_eq!(backward.duration(), forward.duration());
+        for i in 0..=40 {
+            let t = f64::from(i) * 0.1;
+            assert!(close(backward.position_f64(t), -forward.position_f64(t)));
+            assert!(close(backward.speed_f64(t), -forward.speed_f64(t)));
+        }
+        assert_eq!(backward.position(backward.duration()), -500);
+        Ok(())
+    }
+
+    #[test]
+    fn s_curve_ends_at_the_target() -> Ev3Result<()> {
+        let profile = MotionProfile::new(500, 200, 400, 400, ProfileShape::SCurve)?;
+        let end = profile.duration();
+
+        assert_eq!(profile.position(end), 500);
+        assert_eq!(profile.speed(end), 0);
+        assert_eq!(profile.position(end + Duration::from_secs(1)), 500);
+
+        // the speed and position approach the end smoothly
+        let just_before = end.as_secs_f64() - 0.001;
+        assert!(profile.speed_f64(just_before).abs() < 0.01);
+        assert!(close(profile.position_f64(just_before), 500.0));
+
+        // the acceleration starts at zero
+        assert!(profile.speed_f64(0.001) < 0.01);
+        Ok(())
+    }
+
+    #[test]
+    fn stretch_to() -> Ev3Result<()> {
+        let mut profile = MotionProfile::new(500, 200, 400, 400, ProfileShape::Trapezoidal)?;
+
+        // shorter durations are ignored
+        profile.stretch_to(Duration::from_secs(1));
+        assert_eq!(profile.duration(), Duration::from_secs(3));
+
+        profile.stretch_to(Duration::from_secs(6));
+        assert!(close(profile.duration().as_secs_f64(), 6.0));
+        assert!(close(profile.speed_f64(3.0), 100.0));
+        // speeding up covers the same distance in twice the time
+        assert!(close(profile.position_f64(1.0), 50.0));
+        assert!(close(profile.speed_f64(0.5), 50.0));
+        assert_eq!(profile.position(profile.duration()), 500);
+        Ok(())
+    }
+
+    #[test]
+    fn limits_have_to_be_positive() {
+        assert!(MotionProfile::new(500, 0, 400, 400, ProfileShape::Trapezoidal).is_err());
+        assert!(MotionProfile::new(500, 200, -400, 400, ProfileShape::Trapezoidal).is_err());
+        assert!(MotionProfile::new(500, 200, 400, 0, ProfileShape::SCurve).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+
+use crate::error::Ev3Result;
+use crate::pupdevices::{InfraredSensor, UltrasonicSensor};
+use crate::robotics::distance_filter::DistanceFilter;
+
+// how many samples the median filter keeps, few enough to react quickly
+const MEDIAN_SAMPLES: usize = 3;
+
+// the slowest the robot drives while slowing down for an obstacle, as a fraction of the speed
+const MIN_SPEED_FRACTION: f64 = 0.2;
+
+pub(crate) enum ObstacleSensor<'a> {
+    Ultrasonic(&'a UltrasonicSensor),
+    Infrared(&'a InfraredSensor),
+}
+
+/// Watches for obstacles in front of a `DriveBase`, see `DriveBase::with_ultrasonic_guard`.
+pub(crate) struct ObstacleGuard<'a> {
+    sensor: ObstacleSensor<'a>,
+    stop_distance: f64,
+    slow_distance: f64,
+    filter: RefCell<DistanceFilter>,
+}
+
+/// What the robot should do about the obstacles in front of it.
+pub(crate) enum Clearance {
+    /// Drive at this fraction of the planned speed.
+    Clear(f64),
+    /// Stop, there is an obstacle at this distance in mm.
+    Blocked(f64),
+}
+
+impl<'a> ObstacleGuard<'a> {
+    pub(crate) fn new(sensor: ObstacleSensor<'a>, stop_distance: f64, slow_distance: f64) -> Self {
+        Self {
+            sensor,
+            stop_distance,
+            slow_distance: slow_distance.max(stop_distance),
+            filter: RefCell::new(DistanceFilter::new(MEDIAN_SAMPLES)),
+        }
+    }
+
+    // Forgets the old samples, since the robot may have moved since the last drive action
+    pub(crate) fn reset(&self) {
+        self.filter.borrow_mut().clear();
+    }
+
+    pub(crate) fn check(&self) -> Ev3Result<Clearance> {
+        let mut filter = self.filter.borrow_mut();
+        let distance = filter.sample(|| match self.sensor {
+            ObstacleSensor::Ultrasonic(sensor) => sensor.distance_mm(),
+            ObstacleSensor::Infrared(sensor) => sensor.distance_mm(),
+        })?;
+
+        Ok(match distance {
+            // a single spurious sample shouldn't stop the robot
+            Some(distance) if distance <= self.stop_distance && filter.is_full() => {
+                Clearance::Blocked(distance)
+            }
+            Some(distance) if distance <= self.stop_distance => {
+                Clearance::Clear(MIN_SPEED_FRACTION)
+            }
+            Some(distance) if distance < self.slow_distance => Clearance::Clear(
+                ((distance - self.stop_distance) / (self.slow_distance - self.stop_distance))
+                    .max(MIN_SPEED_FRACTION),
+            ),
+            _ => Clearance::Clear(1.0),
+        })
+    }
+}
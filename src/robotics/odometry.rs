@@ -0,0 +1,193 @@
+use fixed::types::I32F32;
+
+/// A position and heading on the field.
+///
+/// The position is in mm and the heading is in degrees.
+/// `x` points forward and `y` points to the right of the robot's starting pose,
+/// and the heading is clockwise, the same as `DriveBase::turn`.
+///
+/// Like the gyro heading, the heading isn't wrapped, so a full turn to the right ends at 360.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    /// The forward position in mm.
+    pub x: I32F32,
+    /// The sideways position in mm, where positive is to the right.
+    pub y: I32F32,
+    /// The heading in degrees, where positive is clockwise.
+    pub heading: I32F32,
+}
+
+impl Pose {
+    /// Creates a new `Pose`.
+    pub fn new<Number>(x: Number, y: Number, heading: Number) -> Self
+    where
+        Number: fixed::traits::ToFixed,
+    {
+        Self {
+            x: I32F32::from_num(x),
+            y: I32F32::from_num(y),
+            heading: I32F32::from_num(heading),
+        }
+    }
+}
+
+/// Integrates wheel distance and heading measurements into a pose.
+///
+/// Floats are used because the fixed point types don't support trigonometry.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Odometry {
+    x: f64,
+    y: f64,
+    heading: f64,
+    distance: f64,
+    // the previous measurements, which the next update is relative to
+    last_distance: Option<f64>,
+    last_heading: Option<f64>,
+}
+
+impl Odometry {
+    /// Adds the movement since the previous measurement.
+    ///
+    /// `wheel_distance` is the average distance travelled by the wheels in mm,
+    /// and `measured_heading` is the heading from the encoders or gyro in degrees.
+    /// Both can have any offset, since only the change is used.
+    pub(crate) fn update(&mut self, wheel_distance: f64, measured_heading: f64) {
+        if let (Some(last_distance), Some(last_heading)) = (self.last_distance, self.last_heading) {
+            let distance = wheel_distance - last_distance;
+            let turn = measured_heading - last_heading;
+
+            // assume the robot drove along an arc, which is the same as driving
+            // in a straight line at the average heading for small steps
+            let mid_heading = (self.heading + turn / 2.0).to_radians();
+            self.x += distance * mid_heading.cos();
+            self.y += distance * mid_heading.sin();
+            self.heading += turn;
+            self.distance += distance;
+        }
+
+        self.sync(wheel_distance, measured_heading);
+    }
+
+    /// Sets the reference measurements without moving the pose.
+    ///
+    /// This is needed when the measurements jump, like when the heading source changes.
+    pub(crate) fn sync(&mut self, wheel_distance: f64, measured_heading: f64) {
+        self.last_distance = Some(wheel_distance);
+        self.last_heading = Some(measured_heading);
+    }
+
+    /// Moves the pose and resets the distance to zero.
+    pub(crate) fn reset(&mut self, pose: Pose) {
+        self.x = pose.x.to_num();
+        self.y = pose.y.to_num();
+        self.heading = pose.heading.to_num();
+        self.distance = 0.0;
+    }
+
+    pub(crate) fn pose(&self) -> Pose {
+        Pose {
+            x: I32F32::from_num(self.x),
+            y: I32F32::from_num(self.y),
+            heading: I32F32::from_num(self.heading),
+        }
+    }
+
+    /// The signed distance travelled since the last reset in mm.
+    pub(crate) fn distance(&self) -> I32F32 {
+        I32F32::from_num(self.distance)
+    }
+}
+
+/// Wraps an angle in degrees to the range [-180, 180).
+pub(crate) fn normalize_angle(angle: f64) -> f64 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_pose(odometry: &Odometry, x: f64, y: f64, heading: f64) {
+        let pose = odometry.pose();
+        let (actual_x, actual_y, actual_heading) = (
+            pose.x.to_num::<f64>(),
+            pose.y.to_num::<f64>(),
+            pose.heading.to_num::<f64>(),
+        );
+        assert!(
+            (actual_x - x).abs() < 0.5 && (actual_y - y).abs() < 0.5,
+            "position ({actual_x}, {actual_y}) instead of ({x}, {y})"
+        );
+        assert!(
+            (actual_heading - heading).abs() < 0.01,
+            "heading {actual_heading} instead of {heading}"
+        );
+    }
+
+    #[test]
+    fn straight() {
+        let mut odometry = Odometry::default();
+
+        // the first measurements are only the reference
+        odometry.update(500.0, 30.0);
+        assert_pose(&odometry, 0.0, 0.0, 0.0);
+
+        odometry.update(800.0, 30.0);
+        assert_pose(&odometry, 300.0, 0.0, 0.0);
+
+        odometry.update(700.0, 30.0);
+        assert_pose(&odometry, 200.0, 0.0, 0.0);
+        assert_eq!(odometry.distance(), 200);
+    }
+
+    #[test]
+    fn arc() {
+        let mut odometry = Odometry::default();
+        let radius = 200.0;
+
+        // a quarter circle to the right, one degree at a time
+        for degrees in 0..=90 {
+            let degrees = f64::from(degrees);
+            odometry.update(radius * degrees.to_radians(), degrees);
+        }
+
+        assert_pose(&odometry, radius, radius, 90.0);
+    }
+
+    #[test]
+    fn reset() {
+        let mut odometry = Odometry::default();
+        odometry.update(0.0, 0.0);
+        odometry.update(100.0, 0.0);
+
+        odometry.reset(Pose::new(10, 20, -90));
+        assert_pose(&odometry, 10.0, 20.0, -90.0);
+        assert_eq!(odometry.distance(), 0);
+
+        // the next update continues from the new pose, with the same reference measurements
+        odometry.update(150.0, 0.0);
+        assert_pose(&odometry, 10.0, -30.0, -90.0);
+        assert_eq!(odometry.distance(), 50);
+    }
+
+    #[test]
+    fn sync_ignores_jumps() {
+        let mut odometry = Odometry::default();
+        odometry.update(0.0, 0.0);
+
+        odometry.sync(1000.0, 45.0);
+        odometry.update(1100.0, 45.0);
+        assert_pose(&odometry, 100.0, 0.0, 0.0);
+    }
+
+    #[test]
+    fn angles_wrap_at_180() {
+        assert_eq!(normalize_angle(45.0), 45.0);
+        assert_eq!(normalize_angle(180.0), -180.0);
+        assert_eq!(normalize_angle(-180.0), -180.0);
+        assert_eq!(normalize_angle(190.0), -170.0);
+        assert_eq!(normalize_angle(-190.0), 170.0);
+        assert_eq!(normalize_angle(540.0), -180.0);
+        assert_eq!(normalize_angle(-360.0), 0.0);
+    }
+}
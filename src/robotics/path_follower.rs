@@ -0,0 +1,278 @@
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+use scopeguard::defer;
+use tokio::time::{Instant, interval};
+
+use crate::error::{Ev3Error, Ev3Result};
+use crate::parameters::Stop;
+use crate::robotics::DriveBase;
+
+// how close the robot has to get to the last waypoint in mm
+const END_TOLERANCE: f64 = 10.0;
+
+// the slowest the robot drives while slowing down for the last waypoint, as a fraction of the speed
+const MIN_SPEED_FRACTION: f64 = 0.2;
+
+/// Drives a `DriveBase` along a path of waypoints with a pure pursuit controller.
+///
+/// The robot continuously steers towards a point on the path that is a fixed distance ahead of it,
+/// called the lookahead distance. A longer lookahead gives a smoother path that cuts corners more,
+/// while a shorter lookahead follows the path more closely but can oscillate.
+///
+/// The waypoints are field coordinates in mm, in the same frame as `DriveBase::pose`.
+/// The path starts at the robot's position when `follow` is called.
+///
+/// The motor ramps are turned off while following the path, since they would delay the steering.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort, Stop};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::robotics::{DriveBase, PathFollower};
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+///
+/// let path = PathFollower::new(&drive, vec![(300, 0), (600, 300), (600, 800)])?
+///     .with_lookahead(120)
+///     .with_speed(250)
+///     .with_stop_action(Stop::Brake);
+///
+/// path.follow().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PathFollower<'a, 'b> {
+    drive: &'b DriveBase<'a>,
+    waypoints: Vec<(f64, f64)>,
+    lookahead: f64,
+    speed: f64,
+    stop_action: Stop,
+}
+
+impl<'a, 'b> PathFollower<'a, 'b> {
+    /// Creates a new `PathFollower` for the given waypoints in mm.
+    ///
+    /// The defaults are a lookahead of 100 mm, a speed of 200 mm/s and a stop action of `Stop::Hold`.
+    ///
+    /// Returns `InvalidValue` if there are no waypoints.
+    pub fn new<Number>(
+        drive: &'b DriveBase<'a>,
+        waypoints: Vec<(Number, Number)>,
+    ) -> Ev3Result<Self>
+    where
+        Number: ToFixed,
+    {
+        if waypoints.is_empty() {
+            return Err(Ev3Error::InvalidValue {
+                func: "PathFollower::new".into(),
+                value: "no waypoints".into(),
+            });
+        }
+
+        Ok(Self {
+            drive,
+            waypoints: waypoints
+                .into_iter()
+                .map(|(x, y)| (I32F32::from_num(x).to_num(), I32F32::from_num(y).to_num()))
+                .collect(),
+            lookahead: 100.0,
+            speed: 200.0,
+            stop_action: Stop::Hold,
+        })
+    }
+
+    /// Sets the lookahead distance in mm.
+    pub fn with_lookahead<Number>(mut self, lookahead: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.lookahead = I32F32::from_num(lookahead).to_num::<f64>().max(1.0);
+        self
+    }
+
+    /// Sets the driving speed in mm/s.
+    ///
+    /// The robot slows down when it gets close to the last waypoint.
+    pub fn with_speed<Number>(mut self, speed: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.speed = I32F32::from_num(speed).to_num::<f64>().abs();
+        self
+    }
+
+    /// Sets how the robot stops at the end of the path.
+    ///
+    /// This doesn't change the stop action of the `DriveBase`.
+    pub fn with_stop_action(mut self, action: Stop) -> Self {
+        self.stop_action = action;
+        self
+    }
+
+    /// Drives along the path until the robot reaches the last waypoint.
+    ///
+    /// The `DriveBase` is stopped with the path's stop action when this finishes or is canceled,
+    /// and the next drive action starts from where the robot stopped.
+    pub async fn follow(&self) -> Ev3Result<()> {
+        defer! {
+            _ = self.drive.stop_with(self.stop_action);
+            _ = self.drive.enable_ramps(true);
+            _ = self.drive.sync_targets();
+        }
+
+        self.drive.enable_ramps(false)?;
+
+        let start = self.drive.pose()?;
+        let mut path = vec![(start.x.to_num::<f64>(), start.y.to_num::<f64>())];
+        path.extend(self.waypoints.iter().copied());
+
+        let end = path[path.len() - 1];
+        let mut segment = 0;
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        loop {
+            self.drive.tick(Instant::now())?;
+
+            let pose = self.drive.pose()?;
+            let position = (pose.x.to_num::<f64>(), pose.y.to_num::<f64>());
+            let heading = pose.heading.to_num::<f64>().to_radians();
+
+            // the last waypoint in the robot's frame, where x is forward and y is to the right
+            let (end_forward, _) = to_robot_frame(position, heading, end);
+            let remaining = distance(position, end);
+
+            let on_last_segment = segment + 2 >= path.len();
+            if remaining < END_TOLERANCE
+                || (on_last_segment && remaining < self.lookahead && end_forward <= 0.0)
+            {
+                return Ok(());
+            }
+
+            let target = lookahead_point(&path, &mut segment, position, self.lookahead);
+            let (forward, right) = to_robot_frame(position, heading, target);
+
+            // the curvature of the arc that goes through the target
+            let chord_squared = forward * forward + right * right;
+            let curvature = if chord_squared > 0.0 {
+                2.0 * right / chord_squared
+            } else {
+                0.0
+            };
+
+            let speed = if remaining < self.lookahead {
+                self.speed * (remaining / self.lookahead).max(MIN_SPEED_FRACTION)
+            } else {
+                self.speed
+            };
+
+            self.drive.run_at(speed, (speed * curvature).to_degrees())?;
+
+            timer.tick().await;
+        }
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+// converts a field point to the robot's frame, as (forward, right)
+fn to_robot_frame(position: (f64, f64), heading: f64, point: (f64, f64)) -> (f64, f64) {
+    let dx = point.0 - position.0;
+    let dy = point.1 - position.1;
+    (
+        dx * heading.cos() + dy * heading.sin(),
+        -dx * heading.sin() + dy * heading.cos(),
+    )
+}
+
+// Finds the point on the path that is `lookahead` away from the robot, starting from the current segment.
+// The segment is moved forward once the robot is within the lookahead of its end, so the robot never goes back.
+fn lookahead_point(
+    path: &[(f64, f64)],
+    segment: &mut usize,
+    position: (f64, f64),
+    lookahead: f64,
+) -> (f64, f64) {
+    while *segment + 1 < path.len() {
+        let start = path[*segment];
+        let end = path[*segment + 1];
+
+        if distance(position, end) < lookahead {
+            if *segment + 2 < path.len() {
+                *segment += 1;
+                continue;
+            }
+            return end;
+        }
+
+        // solve |start + t * (end - start) - position| = lookahead for the furthest t
+        let d = (end.0 - start.0, end.1 - start.1);
+        let f = (start.0 - position.0, start.1 - position.1);
+        let a = d.0 * d.0 + d.1 * d.1;
+        let b = 2.0 * (f.0 * d.0 + f.1 * d.1);
+        let c = f.0 * f.0 + f.1 * f.1 - lookahead * lookahead;
+        let discriminant = b * b - 4.0 * a * c;
+
+        if a > 0.0 && discriminant >= 0.0 {
+            let t = (-b + discriminant.sqrt()) / (2.0 * a);
+            if (0.0..=1.0).contains(&t) {
+                return (start.0 + t * d.0, start.1 + t * d.1);
+            }
+        }
+
+        // the robot is too far from the path to reach it with the lookahead,
+        // so head straight for the end of the segment
+        return end;
+    }
+
+    path[path.len() - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::{Direction, MotorPort};
+    use crate::pupdevices::Motor;
+    use crate::sim::SimDriveBase;
+
+    #[tokio::test(start_paused = true)]
+    async fn next_action_keeps_the_heading() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        PathFollower::new(&drive, vec![(300, 0), (500, 300)])?
+            .follow()
+            .await?;
+
+        let (x, y, heading) = robot.pose();
+        assert!(
+            (x - 500.0).abs() < 30.0 && (y - 300.0).abs() < 30.0,
+            "at ({x}, {y})"
+        );
+
+        drive.straight(100).await?;
+
+        let (end_x, end_y, end_heading) = robot.pose();
+        let travelled = (end_x - x).hypot(end_y - y);
+        assert!(
+            (end_heading - heading).abs() < 2.0,
+            "turned from {heading} to {end_heading}"
+        );
+        assert!((travelled - 100.0).abs() < 10.0, "travelled {travelled}");
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+
+use crate::error::{Ev3Error, Ev3Result};
+
+// keeps the integral within what the output can hold, so a long lasting error can't wind it up forever
+const INTEGRAL_LIMIT: f64 = i32::MAX as f64;
+
+/// How a `Pid` keeps its integral from winding up while the output is at its limits.
+///
+/// Without anti-windup, the integral keeps growing while the output is limited,
+/// and the controller overshoots once the error finally shrinks.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AntiWindup {
+    /// The integral grows freely.
+    None,
+    /// The integral stops growing while the output is limited and the error would push it further.
+    Clamping,
+    /// The integral is pulled back by how far the output is past its limits,
+    /// times the gain in 1/s.
+    ///
+    /// A higher gain unwinds the integral faster. The integral gain is a good starting point.
+    BackCalculation {
+        /// How fast the integral is pulled back, in 1/s.
+        gain: I32F32,
+    },
+}
+
+/// A set of PID gains, like the ones proposed by `RelayResult::gains`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    /// The proportional gain.
+    pub kp: I32F32,
+    /// The integral gain, per second.
+    pub ki: I32F32,
+    /// The derivative gain, in seconds.
+    pub kd: I32F32,
+}
+
+/// A PID controller that drives a measurement to a setpoint.
+///
+/// The output is `kp * error + ki * integral of the error + kd * rate of change`, plus an optional feedforward,
+/// where the error is `setpoint - measurement` and the time is in seconds,
+/// so the gains don't depend on how often the controller is updated.
+///
+/// The derivative is taken on the measurement instead of the error,
+/// so changing the setpoint doesn't make the output jump.
+/// It can be smoothed with a low-pass filter, see `with_derivative_filter`.
+///
+/// This is the controller that `DriveBase` and `LineFollower` use.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use std::time::Duration;
+/// use ev3dev_rs::parameters::{Direction, MotorPort};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::robotics::{AntiWindup, Pid};
+///
+/// let arm = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+///
+/// // hold the arm at 45 degrees with the duty cycle
+/// let pid = Pid::new(2.0, 4.0, 0.05)
+///     .with_setpoint(45)
+///     .with_output_limits(-100, 100)?
+///     .with_anti_windup(AntiWindup::Clamping)
+///     .with_derivative_filter(Duration::from_millis(20));
+///
+/// let mut timer = tokio::time::interval(Duration::from_millis(10));
+/// loop {
+///     timer.tick().await;
+///
+///     // the arm needs about 15% to hold itself up when it is level
+///     let angle = f64::from(arm.angle()?);
+///     let gravity = 15.0 * angle.to_radians().cos();
+///
+///     let output = pid.update_with_feedforward(angle, gravity, Duration::from_millis(10));
+///     arm.dc(output.to_num())?;
+/// }
+/// # }
+/// ```
+pub struct Pid {
+    kp: Cell<f64>,
+    ki: Cell<f64>,
+    kd: Cell<f64>,
+    setpoint: Cell<f64>,
+    output_limits: Cell<Option<(f64, f64)>>,
+    anti_windup: Cell<AntiWindup>,
+    derivative_filter: Cell<f64>,
+    integral_deadzone: Cell<f64>,
+    integral_rate: Cell<f64>,
+    // the integral term, which already includes ki so that changing ki doesn't make the output jump
+    integral: Cell<f64>,
+    derivative: Cell<f64>,
+    prev_measurement: Cell<Option<f64>>,
+}
+
+impl Pid {
+    /// Creates a new `Pid` with the given gains.
+    ///
+    /// The defaults are a setpoint of 0, no output limits, `AntiWindup::Clamping`,
+    /// no derivative filter and no integral deadzone or rate limit.
+    pub fn new<Number>(kp: Number, ki: Number, kd: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        let pid = Self {
+            kp: Cell::new(0.0),
+            ki: Cell::new(0.0),
+            kd: Cell::new(0.0),
+            setpoint: Cell::new(0.0),
+            output_limits: Cell::new(None),
+            anti_windup: Cell::new(AntiWindup::Clamping),
+            derivative_filter: Cell::new(0.0),
+            integral_deadzone: Cell::new(0.0),
+            integral_rate: Cell::new(0.0),
+            integral: Cell::new(0.0),
+            derivative: Cell::new(0.0),
+            prev_measurement: Cell::new(None),
+        };
+        pid.settings(kp, ki, kd);
+        pid
+    }
+
+    /// Sets the setpoint, see `set_setpoint`.
+    pub fn with_setpoint<Number>(self, setpoint: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.set_setpoint(setpoint);
+        self
+    }
+
+    /// Sets the output limits, see `set_output_limits`.
+    pub fn with_output_limits<Number>(self, min: Number, max: Number) -> Ev3Result<Self>
+    where
+        Number: ToFixed,
+    {
+        self.set_output_limits(min, max)?;
+        Ok(self)
+    }
+
+    /// Sets how the integral is kept from winding up while the output is at its limits.
+    pub fn with_anti_windup(self, anti_windup: AntiWindup) -> Self {
+        self.anti_windup.set(anti_windup);
+        self
+    }
+
+    /// Smooths the derivative with a low-pass filter with the given time constant.
+    ///
+    /// Measurements like encoder angles change in steps, which makes the derivative noisy.
+    /// A time constant of a few update periods is usually enough.
+    pub fn with_derivative_filter(self, time_constant: Duration) -> Self {
+        self.derivative_filter.set(time_constant.as_secs_f64());
+        self
+    }
+
+    /// Sets the integral deadzone and rate limit, see `set_integral_limits`.
+    pub fn with_integral_limits<Number>(self, deadzone: Number, rate: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.set_integral_limits(deadzone, rate);
+        self
+    }
+
+    /// Sets the gains.
+    ///
+    /// Floating point numbers or integers can be used.
+    pub fn settings<Number>(&self, kp: Number, ki: Number, kd: Number)
+    where
+        Number: ToFixed,
+    {
+        self.kp.set(I32F32::from_num(kp).to_num());
+        self.ki.set(I32F32::from_num(ki).to_num());
+        self.kd.set(I32F32::from_num(kd).to_num());
+    }
+
+    /// Gets the gains.
+    pub fn gains(&self) -> PidGains {
+        PidGains {
+            kp: I32F32::from_num(self.kp.get()),
+            ki: I32F32::from_num(self.ki.get()),
+            kd: I32F32::from_num(self.kd.get()),
+        }
+    }
+
+    /// Sets the value that the measurement is driven to.
+    pub fn set_setpoint<Number>(&self, setpoint: Number)
+    where
+        Number: ToFixed,
+    {
+        self.setpoint.set(I32F32::from_num(setpoint).to_num());
+    }
+
+    /// Gets the setpoint.
+    pub fn setpoint(&self) -> I32F32 {
+        I32F32::from_num(self.setpoint.get())
+    }
+
+    /// Limits the output to the range from min to max.
+    ///
+    /// Returns `InvalidValue` if min is greater than max.
+    pub fn set_output_limits<Number>(&self, min: Number, max: Number) -> Ev3Result<()>
+    where
+        Number: ToFixed,
+    {
+        let min: f64 = I32F32::from_num(min).to_num();
+        let max: f64 = I32F32::from_num(max).to_num();
+
+        if min > max {
+            return Err(Ev3Error::InvalidValue {
+                func: "Pid::set_output_limits".into(),
+                value: format!("{min} > {max}"),
+            });
+        }
+
+        self.output_limits.set(Some((min, max)));
+        Ok(())
+    }
+
+    /// Sets when the integral grows.
+    ///
+    /// Errors smaller than the deadzone aren't integrated,
+    /// which keeps the integral from hunting around the setpoint.
+    /// The rate limits how fast the integral term may change per second, where 0 means no limit.
+    pub fn set_integral_limits<Number>(&self, deadzone: Number, rate: Number)
+    where
+        Number: ToFixed,
+    {
+        self.integral_deadzone
+            .set(I32F32::from_num(deadzone).to_num::<f64>().abs());
+        self.integral_rate
+            .set(I32F32::from_num(rate).to_num::<f64>().abs());
+    }
+
+    /// Resets the integral and derivative without changing the settings.
+    ///
+    /// This should be done before starting a new move,
+    /// since the integral and the last measurement are from the previous one.
+    pub fn reset(&self) {
+        self.integral.set(0.0);
+        self.derivative.set(0.0);
+        self.prev_measurement.set(None);
+    }
+
+    /// Calculates the next output from the measurement and the time since the last update.
+    ///
+    /// If no time has passed, the output is calculated from the measurement without updating the integral or derivative.
+    /// Without output limits, the output saturates at the limits of `I32F32`.
+    pub fn update<Number>(&self, measurement: Number, dt: Duration) -> I32F32
+    where
+        Number: ToFixed,
+    {
+        self.update_with_feedforward(measurement, 0, dt)
+    }
+
+    /// Calculates the next output like `update`, with the feedforward added to the output.
+    ///
+    /// The feedforward is the output that you expect to need, like the speed of a planned move
+    /// or the power needed to hold up an arm, so that the PID only has to correct the difference.
+    pub fn update_with_feedforward<Number, Feedforward>(
+        &self,
+        measurement: Number,
+        feedforward: Feedforward,
+        dt: Duration,
+    ) -> I32F32
+    where
+        Number: ToFixed,
+        Feedforward: ToFixed,
+    {
+        let measurement: f64 = I32F32::from_num(measurement).to_num();
+        let feedforward: f64 = I32F32::from_num(feedforward).to_num();
+        let dt = dt.as_secs_f64();
+
+        let error = self.setpoint.get() - measurement;
+
+        // the derivative can't be calculated without a time step or a previous measurement,
+        // and without a time step the previous measurement is kept for the next update
+        if dt > 0.0
+            && let Some(prev_measurement) = self.prev_measurement.get()
+        {
+            let raw = -(measurement - prev_measurement) / dt;
+            let alpha = dt / (self.derivative_filter.get() + dt);
+            self.derivative
+                .set(self.derivative.get() + alpha * (raw - self.derivative.get()));
+        }
+        if dt > 0.0 || self.prev_measurement.get().is_none() {
+            self.prev_measurement.set(Some(measurement));
+        }
+
+        let p = self.kp.get() * error;
+        let d = self.kd.get() * self.derivative.get();
+
+        let unlimited = |integral: f64| p + integral + d + feedforward;
+        let limit = |output: f64| match self.output_limits.get() {
+            Some((min, max)) => output.clamp(min, max),
+            None => output,
+        };
+
+        let mut step = if error.abs() > self.integral_deadzone.get() {
+            self.ki.get() * error * dt
+        } else {
+            0.0
+        };
+        if self.integral_rate.get() > 0.0 {
+            let max_step = self.integral_rate.get() * dt;
+            step = step.clamp(-max_step, max_step);
+        }
+
+        let integral = self.integral.get();
+        let integral = match self.anti_windup.get() {
+            AntiWindup::None => integral + step,
+            AntiWindup::Clamping => {
+                let output = unlimited(integral + step);
+                // only grow the integral if that doesn't push the output further past its limits
+                if limit(output) != output && step.signum() == output.signum() {
+                    integral
+                } else {
+                    integral + step
+                }
+            }
+            AntiWindup::BackCalculation { gain } => {
+                let output = unlimited(integral);
+                integral + step + gain.to_num::<f64>() * (limit(output) - output) * dt
+            }
+        };
+        self.integral
+            .set(integral.clamp(-INTEGRAL_LIMIT, INTEGRAL_LIMIT));
+
+        to_fixed(limit(unlimited(self.integral.get())))
+    }
+}
+
+// Converts an output to a fixed point number, saturating instead of panicking when it is out of range
+fn to_fixed(value: f64) -> I32F32 {
+    if value.is_nan() {
+        I32F32::ZERO
+    } else {
+        I32F32::saturating_from_num(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturates_instead_of_panicking() {
+        let pid = Pid::new(1000.0, 1000.0, 1000.0);
+
+        // a huge derivative over a tiny time step
+        pid.update(0, Duration::from_millis(5));
+        assert_eq!(pid.update(-1_000_000, Duration::from_nanos(1)), I32F32::MAX);
+
+        // an error that lasts a long time
+        for _ in 0..100 {
+            assert_eq!(
+                pid.update(-1_000_000, Duration::from_secs(1000)),
+                I32F32::MAX
+            );
+        }
+    }
+
+    #[test]
+    fn no_time_step_keeps_the_integral_and_derivative() {
+        let pid = Pid::new(1.0, 1.0, 1.0);
+        pid.update(0, Duration::from_secs(1));
+        let output = pid.update(-1, Duration::from_secs(1));
+
+        assert_eq!(pid.update(-1, Duration::ZERO), output);
+        assert_eq!(pid.integral.get(), 1.0);
+    }
+}
@@ -0,0 +1,297 @@
+use std::f64::consts::PI;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+use scopeguard::defer;
+use tokio::time::{Instant, interval};
+
+use crate::error::{Ev3Error, Ev3Result};
+use crate::pupdevices::Motor;
+use crate::robotics::DriveBase;
+use crate::robotics::pid::PidGains;
+
+/// The rule for turning the result of a relay experiment into PID gains.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TuningRule {
+    /// The classic Ziegler–Nichols rule, which reacts quickly but overshoots noticeably.
+    ZieglerNichols,
+    /// The Tyreus–Luyben rule, which is slower but overshoots much less.
+    TyreusLuyben,
+}
+
+/// The oscillation measured by a `RelayTuner`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RelayResult {
+    /// The gain at which a proportional controller would just keep oscillating.
+    pub ultimate_gain: I32F32,
+    /// The period of that oscillation.
+    pub ultimate_period: Duration,
+    /// The measured amplitude of the oscillation, in the units of the measurement.
+    pub amplitude: I32F32,
+}
+
+impl RelayResult {
+    /// Proposes PID gains with the given rule.
+    pub fn gains(&self, rule: TuningRule) -> PidGains {
+        let ku = self.ultimate_gain.to_num::<f64>();
+        let tu = self.ultimate_period.as_secs_f64();
+
+        // the gain, and the integral and derivative times as fractions of the period
+        let (kp, ti, td) = match rule {
+            TuningRule::ZieglerNichols => (0.6 * ku, tu / 2.0, tu / 8.0),
+            TuningRule::TyreusLuyben => (ku / 2.2, 2.2 * tu, tu / 6.3),
+        };
+
+        PidGains {
+            kp: I32F32::saturating_from_num(kp),
+            ki: I32F32::saturating_from_num(kp / ti),
+            kd: I32F32::saturating_from_num(kp * td),
+        }
+    }
+}
+
+/// Measures how a `Motor` or a `DriveBase` responds with a relay experiment, for tuning PIDs.
+///
+/// The relay switches between a positive and a negative speed whenever the angle crosses its starting angle,
+/// which makes it oscillate around the start.
+/// The period and amplitude of the oscillation give the ultimate gain and period,
+/// which `RelayResult::gains` turns into PID gains.
+///
+/// The speed is in degrees per second, so the gains from `tune_heading` are in the units of
+/// `DriveBase::heading_pid_settings`, and the gains from `tune_motor` on a drive wheel
+/// are in the units of `DriveBase::distance_pid_settings`.
+///
+/// The robot has to be free to move back and forth a little during the experiment.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use fixed::types::I32F32;
+/// use ev3dev_rs::parameters::{Direction, MotorPort};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::robotics::{DriveBase, RelayTuner, TuningRule};
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+///
+/// let tuner = RelayTuner::new(100);
+///
+/// let heading = tuner.tune_heading(&drive).await?.gains(TuningRule::TyreusLuyben);
+/// drive.heading_pid_settings(heading.kp, heading.ki, heading.kd, I32F32::ZERO, I32F32::ZERO);
+///
+/// let distance = tuner.tune_motor(&left).await?.gains(TuningRule::TyreusLuyben);
+/// drive.distance_pid_settings(distance.kp, distance.ki, distance.kd, I32F32::ZERO, I32F32::ZERO);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RelayTuner {
+    speed: f64,
+    hysteresis: f64,
+    cycles: usize,
+    timeout: Duration,
+}
+
+impl RelayTuner {
+    /// Creates a new `RelayTuner` that switches between the given speed and its negative, in degrees per second.
+    ///
+    /// A higher speed gives a bigger oscillation, which is measured more accurately but moves the robot further.
+    ///
+    /// The defaults are a hysteresis of 1 degree, 4 measured cycles and a timeout of 20 seconds.
+    pub fn new<Number>(speed: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        Self {
+            speed: I32F32::from_num(speed).to_num::<f64>().abs(),
+            hysteresis: 1.0,
+            cycles: 4,
+            timeout: Duration::from_secs(20),
+        }
+    }
+
+    /// Sets how far in degrees the angle has to cross the start before the relay switches.
+    ///
+    /// This keeps noise from switching the relay back and forth, and should be a bit bigger than the noise.
+    pub fn with_hysteresis<Number>(mut self, hysteresis: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.hysteresis = I32F32::from_num(hysteresis).to_num::<f64>().abs();
+        self
+    }
+
+    /// Sets how many cycles of the oscillation are averaged.
+    ///
+    /// The first cycle is always ignored, since the oscillation hasn't settled yet.
+    pub fn with_cycles(mut self, cycles: usize) -> Self {
+        self.cycles = cycles.max(1);
+        self
+    }
+
+    /// Sets how long the experiment may take before it stops with `Ev3Error::TimedOut`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Runs the experiment on the angle of a `Motor`, switching its speed.
+    ///
+    /// The motor is stopped with its stop action once the experiment is done.
+    pub async fn tune_motor(&self, motor: &Motor) -> Ev3Result<RelayResult> {
+        defer! {
+            _ = motor.stop_prev_action()
+        }
+
+        self.run(
+            || Ok(f64::from(motor.angle()?)),
+            |speed| motor.run(speed.round() as i32),
+        )
+        .await
+    }
+
+    /// Runs the experiment on the heading of a `DriveBase`, switching its turn rate while turning in place.
+    ///
+    /// The heading uses the gyro if `use_gyro` is enabled, like the drive actions.
+    /// The `DriveBase` is stopped with its stop action once the experiment is done,
+    /// and the next drive action starts from where the robot stopped.
+    pub async fn tune_heading(&self, drive: &DriveBase<'_>) -> Ev3Result<RelayResult> {
+        defer! {
+            _ = drive.stop();
+            _ = drive.enable_ramps(true);
+        }
+
+        drive.enable_ramps(false)?;
+
+        let result = self
+            .run(
+                || {
+                    drive.tick(Instant::now())?;
+                    Ok(drive.current_heading()?.to_num())
+                },
+                |turn_rate| drive.run_at(0.0, turn_rate),
+            )
+            .await;
+
+        drive.sync_targets()?;
+        result
+    }
+
+    // Switches the output between the speed and its negative around the starting measurement,
+    // and measures the period and amplitude of the oscillation
+    async fn run<R, W>(&self, mut read: R, mut write: W) -> Ev3Result<RelayResult>
+    where
+        R: FnMut() -> Ev3Result<f64>,
+        W: FnMut(f64) -> Ev3Result<()>,
+    {
+        let start = read()?;
+        let started_at = Instant::now();
+
+        let mut output = self.speed;
+        write(output)?;
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        // each cycle starts when the relay switches back to positive
+        let mut cycle_start: Option<Instant> = None;
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        let mut periods = Vec::with_capacity(self.cycles + 1);
+        let mut amplitudes = Vec::with_capacity(self.cycles + 1);
+
+        while periods.len() <= self.cycles {
+            if started_at.elapsed() >= self.timeout {
+                return Err(Ev3Error::TimedOut {
+                    timeout: self.timeout,
+                });
+            }
+
+            timer.tick().await;
+
+            let value = read()? - start;
+            min = min.min(value);
+            max = max.max(value);
+
+            if output > 0.0 && value > self.hysteresis {
+                output = -self.speed;
+                write(output)?;
+            } else if output < 0.0 && value < -self.hysteresis {
+                output = self.speed;
+                write(output)?;
+
+                let now = Instant::now();
+                if let Some(cycle_start) = cycle_start {
+                    periods.push((now - cycle_start).as_secs_f64());
+                    amplitudes.push((max - min) / 2.0);
+                }
+                cycle_start = Some(now);
+                (min, max) = (f64::INFINITY, f64::NEG_INFINITY);
+            }
+        }
+
+        // the first cycle hasn't settled yet
+        let average = |values: &[f64]| values.iter().sum::<f64>() / values.len() as f64;
+        let period = average(&periods[1..]);
+        let amplitude = average(&amplitudes[1..]);
+
+        // the describing function of a relay with hysteresis
+        let ultimate_gain = 4.0 * self.speed
+            / (PI * (amplitude * amplitude - self.hysteresis * self.hysteresis).sqrt());
+
+        Ok(RelayResult {
+            ultimate_gain: I32F32::saturating_from_num(ultimate_gain),
+            ultimate_period: Duration::from_secs_f64(period),
+            amplitude: I32F32::from_num(amplitude),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::{Direction, MotorPort};
+    use crate::sim::SimDriveBase;
+
+    fn assert_gains(result: RelayResult) {
+        assert!(
+            result.amplitude > 0 && result.ultimate_period > Duration::ZERO,
+            "measured {result:?}"
+        );
+        for rule in [TuningRule::ZieglerNichols, TuningRule::TyreusLuyben] {
+            let gains = result.gains(rule);
+            for gain in [gains.kp, gains.ki, gains.kd] {
+                // saturated gains come from an oscillation too small to measure
+                assert!(
+                    gain > 0 && gain < I32F32::MAX,
+                    "{rule:?} gave {gains:?} from {result:?}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heading_gains_are_positive() -> Ev3Result<()> {
+        let _robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        assert_gains(RelayTuner::new(100).tune_heading(&drive).await?);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn motor_gains_are_positive() -> Ev3Result<()> {
+        let _robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+
+        assert_gains(RelayTuner::new(200).tune_motor(&left).await?);
+        Ok(())
+    }
+}
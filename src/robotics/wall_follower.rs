@@ -0,0 +1,328 @@
+use std::future::Future;
+use std::time::Duration;
+
+use fixed::traits::ToFixed;
+use fixed::types::I32F32;
+use scopeguard::defer;
+use tokio::time::{Instant, interval};
+
+use crate::Race;
+use crate::error::Ev3Result;
+use crate::parameters::{Side, Stop};
+use crate::pupdevices::UltrasonicSensor;
+use crate::robotics::DriveBase;
+use crate::robotics::distance_filter::DistanceFilter;
+
+// how many samples the median filter keeps
+const MEDIAN_SAMPLES: usize = 7;
+
+// how fast the robot turns towards the approach angle, in degrees per second for each degree of error
+const HEADING_GAIN: f64 = 5.0;
+
+/// Keeps a `DriveBase` at a distance from a wall on its side with an `UltrasonicSensor`.
+///
+/// The sensor has to point sideways at the wall.
+///
+/// The ultrasonic sensor sometimes reports spurious readings, like a missing echo or a far away object,
+/// so the distance is sampled every 50 ms and taken as the median of the last 7 samples.
+///
+/// The wall is assumed to be parallel to the robot when following starts.
+/// The robot approaches the target distance at an angle to the wall that grows with the distance error,
+/// and holds that angle with the heading of the `DriveBase`, which uses the gyro if `use_gyro` is enabled.
+/// Walls that curve gently are followed with a small offset from the target distance.
+///
+/// The motor ramps are turned off while following the wall, since they would delay the steering.
+/// Once following ends, the next drive action starts from where the robot stopped.
+///
+/// # Examples
+///
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort, Side};
+/// use ev3dev_rs::pupdevices::{Motor, UltrasonicSensor};
+/// use ev3dev_rs::robotics::{DriveBase, WallFollower};
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::CounterClockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::CounterClockwise)?;
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?;
+/// let ultrasonic_sensor = UltrasonicSensor::new(SensorPort::In2)?;
+///
+/// // keep 100 mm from the wall on the right until the wall ends
+/// let wall = WallFollower::new(&drive, &ultrasonic_sensor, Side::Right)
+///     .with_target_distance(100)
+///     .with_speed(200);
+/// wall.follow_to_end().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct WallFollower<'a, 'b, 'c> {
+    drive: &'b DriveBase<'a>,
+    sensor: &'c UltrasonicSensor,
+    side: Side,
+    target_distance: f64,
+    end_distance: f64,
+    speed: f64,
+    gain: f64,
+    max_angle: f64,
+    stop_action: Stop,
+}
+
+impl<'a, 'b, 'c> WallFollower<'a, 'b, 'c> {
+    /// Creates a new `WallFollower` for a wall on the given side of the robot.
+    ///
+    /// The defaults are a target distance of 150 mm, an end distance of 400 mm,
+    /// a speed of 150 mm/s and a stop action of `Stop::Hold`.
+    pub fn new(drive: &'b DriveBase<'a>, sensor: &'c UltrasonicSensor, side: Side) -> Self {
+        Self {
+            drive,
+            sensor,
+            side,
+            target_distance: 150.0,
+            end_distance: 400.0,
+            speed: 150.0,
+            gain: 0.3,
+            max_angle: 30.0,
+            stop_action: Stop::Hold,
+        }
+    }
+
+    /// Sets the distance to keep from the wall in mm.
+    pub fn with_target_distance<Number>(mut self, target_distance: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.target_distance = I32F32::from_num(target_distance).to_num();
+        self
+    }
+
+    /// Sets the distance in mm at which the wall counts as ended, see `follow_to_end`.
+    pub fn with_end_distance<Number>(mut self, end_distance: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.end_distance = I32F32::from_num(end_distance).to_num();
+        self
+    }
+
+    /// Sets the driving speed in mm/s.
+    pub fn with_speed<Number>(mut self, speed: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.speed = I32F32::from_num(speed).to_num();
+        self
+    }
+
+    /// Sets how sharply the robot steers back to the target distance.
+    ///
+    /// The gain is the angle to the wall in degrees for each mm of distance error,
+    /// and the max angle limits how steeply the robot approaches or leaves the wall.
+    ///
+    /// The defaults are a gain of 0.3 and a max angle of 30 degrees.
+    pub fn with_steering<Number>(mut self, gain: Number, max_angle: Number) -> Self
+    where
+        Number: ToFixed,
+    {
+        self.gain = I32F32::from_num(gain).to_num::<f64>().abs();
+        self.max_angle = I32F32::from_num(max_angle).to_num::<f64>().abs();
+        self
+    }
+
+    /// Sets how the robot stops when it is done following the wall.
+    ///
+    /// This doesn't change the stop action of the `DriveBase`.
+    pub fn with_stop_action(mut self, action: Stop) -> Self {
+        self.stop_action = action;
+        self
+    }
+
+    /// Follows the wall for the given distance in mm, and returns the distance travelled in mm.
+    pub async fn follow_distance<Number>(&self, distance: Number) -> Ev3Result<I32F32>
+    where
+        Number: ToFixed,
+    {
+        let distance = I32F32::from_num(distance).abs();
+        let start = self.drive.distance()?;
+
+        self.follow_while(|_| Ok((self.drive.distance()? - start).abs() < distance))
+            .await
+    }
+
+    /// Follows the wall until it ends, and returns the distance travelled in mm.
+    ///
+    /// The wall ends when the filtered distance is further than the end distance,
+    /// or when the sensor doesn't hear an echo for most of the last 7 samples.
+    pub async fn follow_to_end(&self) -> Ev3Result<I32F32> {
+        self.follow_while(|wall| Ok(wall.is_some_and(|wall| wall < self.end_distance)))
+            .await
+    }
+
+    /// Follows the wall until the condition completes, and returns the distance travelled in mm.
+    ///
+    /// See `DriveBase::straight_until`.
+    pub async fn follow_until<C>(&self, condition: C) -> Ev3Result<I32F32>
+    where
+        C: Future<Output = Ev3Result<()>>,
+    {
+        let start = self.drive.distance()?;
+
+        // following stops the robot when it is canceled by the condition
+        (self.follow_while(|_| Ok(true)), async {
+            condition.await?;
+            Ok(I32F32::ZERO)
+        })
+            .race()
+            .await?;
+
+        Ok(self.drive.distance()? - start)
+    }
+
+    // Follows the wall as long as the condition returns true for the filtered distance,
+    // and returns the distance travelled
+    async fn follow_while<F>(&self, mut condition: F) -> Ev3Result<I32F32>
+    where
+        F: FnMut(Option<f64>) -> Ev3Result<bool>,
+    {
+        defer! {
+            _ = self.drive.stop_with(self.stop_action);
+            _ = self.drive.enable_ramps(true);
+            _ = self.drive.sync_targets();
+        }
+
+        self.drive.enable_ramps(false)?;
+
+        let start = self.drive.distance()?;
+        let max_turn_rate = self.drive.max_turn_rate();
+
+        let wall_heading = self.drive.angle()?.to_num::<f64>();
+        let mut filter = DistanceFilter::new(MEDIAN_SAMPLES);
+
+        let mut timer = interval(Duration::from_millis(5));
+
+        // the first tick completes immediately
+        timer.tick().await;
+
+        // fill the filter before deciding anything, the robot keeps still meanwhile
+        while !filter.is_full() {
+            filter.sample(|| self.sensor.distance_mm())?;
+            timer.tick().await;
+        }
+
+        loop {
+            self.drive.tick(Instant::now())?;
+
+            let wall = filter.sample(|| self.sensor.distance_mm())?;
+
+            if !condition(wall)? {
+                break;
+            }
+
+            let heading = self.drive.angle()?.to_num::<f64>();
+            let target_heading = self.target_heading(wall, heading, wall_heading);
+            let turn =
+                (HEADING_GAIN * (target_heading - heading)).clamp(-max_turn_rate, max_turn_rate);
+
+            self.drive.run_at(self.speed, turn)?;
+
+            timer.tick().await;
+        }
+
+        Ok(self.drive.distance()? - start)
+    }
+
+    // The heading that brings the robot back to the target distance
+    fn target_heading(&self, wall: Option<f64>, heading: f64, wall_heading: f64) -> f64 {
+        let Some(wall) = wall else {
+            // drive along the wall until it is back
+            return wall_heading;
+        };
+
+        // the sensor measures at an angle when the robot isn't parallel to the wall
+        let angle = heading - wall_heading;
+        let distance = wall * angle.to_radians().cos();
+
+        // turning towards the wall is clockwise when the wall is on the right
+        let towards_wall = match self.side {
+            Side::Left => -1.0,
+            Side::Right => 1.0,
+        };
+
+        let approach =
+            (self.gain * (distance - self.target_distance)).clamp(-self.max_angle, self.max_angle);
+        wall_heading + towards_wall * approach
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::parameters::{Direction, MotorPort, SensorPort};
+    use crate::pupdevices::Motor;
+    use crate::sensor_driver::{SensorMode, SensorType};
+    use crate::sim::{SimDriveBase, TestSensor};
+
+    // where the wall on the right is, and where it ends (mm)
+    const WALL_Y: f64 = 300.0;
+    const WALL_END: f64 = 800.0;
+
+    // the distance to the wall in tenths of cm, like the sensor reports it
+    fn echo(robot: &SimDriveBase) -> i32 {
+        let (x, y, heading) = robot.pose();
+        if x > WALL_END {
+            return 2550;
+        }
+        ((WALL_Y - y) / heading.to_radians().cos()).round() as i32
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn follows_the_wall_and_syncs_the_targets() -> Ev3Result<()> {
+        let robot = Arc::new(SimDriveBase::new(
+            MotorPort::OutA,
+            MotorPort::OutD,
+            56.0,
+            120.0,
+        ));
+        let _sensor = TestSensor::new(
+            SensorPort::In2,
+            SensorType::Ultrasonic,
+            SensorMode::UltrasonicDistanceCm,
+            {
+                let robot = robot.clone();
+                move || echo(&robot)
+            },
+        );
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+        let sensor = UltrasonicSensor::new(SensorPort::In2)?;
+
+        WallFollower::new(&drive, &sensor, Side::Right)
+            .follow_to_end()
+            .await?;
+
+        let (x, y, heading) = robot.pose();
+        assert!((x - WALL_END).abs() < 50.0, "stopped at {x}");
+        assert!(
+            (WALL_Y - y - 150.0).abs() < 20.0,
+            "{} mm from the wall",
+            WALL_Y - y
+        );
+        assert!(heading.abs() < 5.0, "heading {heading}");
+
+        drive.straight(100).await?;
+
+        let (end_x, _, end_heading) = robot.pose();
+        assert!(
+            (end_heading - heading).abs() < 2.0,
+            "turned from {heading} to {end_heading}"
+        );
+        assert!(
+            (end_x - x - 100.0).abs() < 10.0,
+            "drove from {x} to {end_x}"
+        );
+        Ok(())
+    }
+}
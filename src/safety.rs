@@ -0,0 +1,219 @@
+use std::{
+    ffi::c_int,
+    io,
+    sync::{
+        Mutex, MutexGuard, Once,
+        atomic::{AtomicI32, AtomicU64, Ordering},
+    },
+    thread,
+};
+
+use crate::{
+    attribute::{Attribute, AttributeName},
+    enum_string::AsStr,
+    error::Ev3Result,
+    motor_driver::MotorDriver,
+    parameters::Stop,
+};
+
+struct Entry {
+    id: u64,
+    command: Attribute,
+    stop_action: Attribute,
+}
+
+impl Entry {
+    fn stop(&self, action: Stop) -> Ev3Result<()> {
+        self.stop_action.set(action.as_str())?;
+        self.command.set("stop")
+    }
+}
+
+static REGISTRY: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static STOP_ACTION: Mutex<Stop> = Mutex::new(Stop::Coast);
+static INSTALL: Once = Once::new();
+
+// the write end of the pipe that the signal handler uses to wake the signal thread
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+// the registry is also used from the panic hook, so a poisoned lock is recovered
+// instead of panicking again
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Sets the stop action that is used when the motors are stopped by the safety layer.
+///
+/// The default is `Stop::Coast`.
+pub fn set_stop_action(action: Stop) {
+    *lock(&STOP_ACTION) = action;
+}
+
+/// Gets the stop action that is used when the motors are stopped by the safety layer.
+pub fn stop_action() -> Stop {
+    *lock(&STOP_ACTION)
+}
+
+/// Stops every open `Motor` with the safety stop action.
+///
+/// Tries to stop every motor even if some of them fail, and returns the first error.
+///
+/// Every `Motor` is registered with the safety layer when it is created, and is stopped when:
+///
+/// * the `Motor` is dropped
+/// * the program panics
+/// * the program receives `SIGINT` (Ctrl-C) or `SIGTERM`
+///
+/// This keeps a motor from spinning forever after `Motor::run` or `Motor::dc`
+/// if the program ends unexpectedly.
+///
+/// The panic hook and signal handlers are installed when the first `Motor` is created.
+/// The panic hook calls the previously installed hook after stopping the motors,
+/// and the signal handlers are only installed if the signals don't already have a handler.
+/// After stopping the motors on a signal, the default handler is restored and the signal is raised again.
+///
+/// # Examples
+/// ``` no_run
+/// # fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort, Stop};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::safety;
+///
+/// // brake instead of coasting when stopping in an emergency
+/// safety::set_stop_action(Stop::Brake);
+///
+/// let motor = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+/// motor.run(500)?;
+///
+/// // the motor is stopped here, even if the program panics before this
+/// drop(motor);
+/// # Ok(())
+/// # }
+/// ```
+pub fn stop_all() -> Ev3Result<()> {
+    let action = stop_action();
+    let mut result = Ok(());
+
+    for entry in lock(&REGISTRY).iter() {
+        let stopped = entry.stop(action);
+        if result.is_ok() {
+            result = stopped;
+        }
+    }
+
+    result
+}
+
+/// Keeps a motor in the registry, and stops it when dropped.
+pub(crate) struct Registration {
+    id: u64,
+}
+
+pub(crate) fn register(driver: &MotorDriver) -> Ev3Result<Registration> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    let entry = Entry {
+        id,
+        command: driver.open_attribute(AttributeName::Command)?,
+        stop_action: driver.open_attribute(AttributeName::StopAction)?,
+    };
+
+    lock(&REGISTRY).push(entry);
+    INSTALL.call_once(install);
+
+    Ok(Registration { id })
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut registry = lock(&REGISTRY);
+        if let Some(index) = registry.iter().position(|entry| entry.id == self.id) {
+            let entry = registry.swap_remove(index);
+            drop(registry);
+            _ = entry.stop(stop_action());
+        }
+    }
+}
+
+fn install() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        _ = stop_all();
+        previous(info);
+    }));
+
+    install_signal_handlers();
+}
+
+extern "C" fn handle_signal(signal: c_int) {
+    // only async-signal-safe functions are allowed here,
+    // so the actual work is done by the signal thread
+    let byte = signal as u8;
+
+    // SAFETY: write is async-signal-safe and the buffer is a single valid byte
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::Relaxed),
+            (&raw const byte).cast(),
+            1,
+        );
+    }
+}
+
+fn install_signal_handlers() {
+    let mut fds: [c_int; 2] = [-1; 2];
+
+    // SAFETY: fds has room for both ends of the pipe
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return;
+    }
+    let [read_fd, write_fd] = fds;
+    SIGNAL_PIPE.store(write_fd, Ordering::Relaxed);
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: both sigaction structs are valid for the duration of the calls,
+        // and handle_signal only calls async-signal-safe functions
+        unsafe {
+            let mut previous: libc::sigaction = std::mem::zeroed();
+            if libc::sigaction(signal, std::ptr::null(), &mut previous) != 0
+                || previous.sa_sigaction != libc::SIG_DFL
+            {
+                // leave handlers that were installed by someone else alone
+                continue;
+            }
+
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_signal as *const () as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+
+    thread::spawn(move || {
+        loop {
+            let mut byte = 0u8;
+
+            // SAFETY: the buffer is a single valid byte
+            let read = unsafe { libc::read(read_fd, (&raw mut byte).cast(), 1) };
+            if read == -1 && io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            if read != 1 {
+                // the pipe is broken, so no signal can arrive anymore
+                break;
+            }
+
+            _ = stop_all();
+
+            // let the default handler end the program, like it would have without us
+            let signal = byte as c_int;
+
+            // SAFETY: restoring the default handler and raising a signal have no preconditions
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        }
+    });
+}
@@ -1,14 +1,21 @@
-use crate::attribute::{Attribute, AttributeName, FileMode};
+use crate::attribute::{Attribute, AttributeName, FileMode, VirtualDevice};
 use crate::crate_enum_str;
 use crate::enum_string::AsStr;
 use crate::error::{Ev3Error, Ev3Result};
 use crate::parameters::SensorPort;
+use crate::replay;
+use crate::sim;
+use crate::tools::device_root;
 use std::cell::{Cell, RefCell};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::{collections::HashMap, fs};
+use std::sync::Arc;
 
-const SENSOR_DIR: &str = "/sys/class/lego-sensor";
+const SENSOR_DIR: &str = "sys/class/lego-sensor";
+
+// simulated devices have no files, but their attributes are recorded under this directory
+const SIM_DIR: &str = "sim";
 
 crate_enum_str! {
     SensorType,
@@ -61,25 +68,57 @@ crate_enum_str! {
 
 pub(crate) struct SensorDriver {
     base_path: PathBuf,
+    device: Option<Arc<dyn VirtualDevice>>,
     attributes: RefCell<HashMap<AttributeName, Attribute>>,
     pub(crate) mode: Cell<SensorMode>,
 }
 
 impl SensorDriver {
     pub(crate) fn new(sensor_type: SensorType, port: SensorPort) -> Ev3Result<Self> {
-        if let Ok(entries) = fs::read_dir(SENSOR_DIR) {
-            for entry in entries {
-                if let Ok(direntry) = entry.map(|e| e.path().to_path_buf())
-                    && let Ok(address) = SensorPort::from_str(
-                        fs::read_to_string(direntry.join("address"))
-                            .or(Err(Ev3Error::InvalidPath))?
-                            .trim(),
-                    )
-                    && let Ok(driver) = SensorType::from_str(
-                        fs::read_to_string(direntry.join("driver_name"))
-                            .or(Err(Ev3Error::InvalidPath))?
-                            .trim(),
-                    )
+        // simulated sensors take priority over the device tree
+        if let Some(device) = sim::find_sensor(port) {
+            let driver = SensorType::from_str(&device.read(AttributeName::DriverName)?)?;
+            if driver != sensor_type {
+                return Err(Ev3Error::IncorrectSensorType {
+                    expected: sensor_type,
+                    found: driver,
+                });
+            }
+
+            let base_path = Path::new(SIM_DIR).join(SENSOR_DIR).join(port.as_str());
+            let mode_attr = Attribute::new_virtual(
+                device.clone(),
+                base_path.join(AttributeName::Mode.to_string()),
+                AttributeName::Mode,
+            );
+            let mode = SensorMode::from_str(&mode_attr.get()?)?;
+
+            let mut attributes = HashMap::new();
+            attributes.insert(AttributeName::Mode, mode_attr);
+
+            return Ok(Self {
+                base_path,
+                device: Some(device),
+                attributes: RefCell::new(attributes),
+                mode: Cell::new(mode),
+            });
+        }
+
+        Self::new_with_root(sensor_type, port, &device_root())
+    }
+
+    pub(crate) fn new_with_root(
+        sensor_type: SensorType,
+        port: SensorPort,
+        root: &Path,
+    ) -> Ev3Result<Self> {
+        if let Some(entries) = replay::read_dir(&root.join(SENSOR_DIR)) {
+            for direntry in entries {
+                if let Ok(address) =
+                    SensorPort::from_str(&replay::read_to_string(&direntry.join("address"))?)
+                    && let Ok(driver) = SensorType::from_str(&replay::read_to_string(
+                        &direntry.join("driver_name"),
+                    )?)
                     && address == port
                 {
                     return if driver == sensor_type {
@@ -95,6 +134,7 @@ impl SensorDriver {
 
                         Ok(Self {
                             base_path: direntry,
+                            device: None,
                             attributes: RefCell::new(attributes),
                             mode: Cell::new(mode),
                         })
@@ -114,13 +154,24 @@ impl SensorDriver {
         })
     }
 
+    fn open_attribute(&self, name: AttributeName) -> Ev3Result<Attribute> {
+        match self.device {
+            Some(ref device) => Ok(Attribute::new_virtual(
+                device.clone(),
+                self.base_path.join(name.to_string()),
+                name,
+            )),
+            None => Attribute::new(self.base_path.join(name.to_string()), name.filemode()),
+        }
+    }
+
     pub(crate) fn read_attribute(&self, name: AttributeName) -> Ev3Result<String> {
         if let Some(attr) = self.attributes.borrow().get(&name) {
             attr.get()
         } else {
             // if the value is not in the hashmap, create a new attribute,
             // get its current value, and insert it into the hashmap
-            let attr = Attribute::new(self.base_path.join(name.to_string()), name.filemode())?;
+            let attr = self.open_attribute(name)?;
             let val = attr.get()?;
             _ = self.attributes.borrow_mut().insert(name, attr);
             Ok(val)
@@ -136,7 +187,7 @@ impl SensorDriver {
         } else {
             // if the value is not in the hashmap, create a new attribute,
             // set its value, and insert it into the hashmap
-            let attr = Attribute::new(self.base_path.join(name.to_string()), name.filemode())?;
+            let attr = self.open_attribute(name)?;
             attr.set(value.as_str())?;
             _ = self.attributes.borrow_mut().insert(name, attr);
             Ok(())
@@ -0,0 +1,308 @@
+use std::{
+    f64::consts::PI,
+    sync::{Arc, Mutex, Weak},
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    parameters::{Direction, MotorPort, SensorPort},
+    sim::{STEP, SimGyroSensor, SimMotor, Simulation},
+};
+
+struct WorldState {
+    last_update: Instant,
+    elapsed: f64,
+
+    wheel_diameter: f64,
+    axle_track: f64,
+    left_sign: f64,
+    right_sign: f64,
+    left_slip: f64,
+    right_slip: f64,
+    gyro_drift: f64,
+
+    left_angle: f64,
+    right_angle: f64,
+    x: f64,
+    y: f64,
+    heading: f64,
+}
+
+struct World {
+    state: Mutex<WorldState>,
+    left: SimMotor,
+    right: SimMotor,
+    gyro: Mutex<Option<SimGyroSensor>>,
+}
+
+impl Simulation for World {
+    fn advance(&self, now: Instant) {
+        let mut state = self.state.lock().expect("Tried to use a poisoned lock");
+        let mut left = self.left.device().lock_model();
+        let mut right = self.right.device().lock_model();
+        let gyro = self.gyro.lock().expect("Tried to use a poisoned lock");
+        let mut gyro = gyro.as_ref().map(|gyro| gyro.device().lock_model());
+
+        let mut time = state.last_update;
+        left.advance_to(time);
+        right.advance_to(time);
+
+        let mut remaining = now.saturating_duration_since(time).as_secs_f64();
+        state.last_update = now;
+
+        let mm_per_degree = PI * state.wheel_diameter / 360.0;
+
+        while remaining > 0.0 {
+            let dt = remaining.min(STEP);
+            remaining -= dt;
+            time += std::time::Duration::from_secs_f64(dt);
+            state.elapsed += dt;
+
+            left.advance_to(time);
+            right.advance_to(time);
+
+            let left_distance = (left.position() - state.left_angle)
+                * state.left_sign
+                * mm_per_degree
+                * (1.0 - state.left_slip);
+            let right_distance = (right.position() - state.right_angle)
+                * state.right_sign
+                * mm_per_degree
+                * (1.0 - state.right_slip);
+
+            state.left_angle = left.position();
+            state.right_angle = right.position();
+
+            // the heading is clockwise, so a left wheel that goes further turns the robot to the right
+            let distance = (left_distance + right_distance) / 2.0;
+            let turn = (left_distance - right_distance) / state.axle_track;
+
+            let mid_heading = state.heading.to_radians() + turn / 2.0;
+            state.x += distance * mid_heading.cos();
+            state.y += distance * mid_heading.sin();
+            state.heading += turn.to_degrees();
+
+            if let Some(ref mut gyro) = gyro {
+                gyro.set(
+                    state.heading + state.gyro_drift * state.elapsed,
+                    turn.to_degrees() / dt + state.gyro_drift,
+                );
+            }
+        }
+
+        // make sure the motors end up exactly on the requested time
+        left.advance_to(now);
+        right.advance_to(now);
+    }
+}
+
+/// A simulated differential drive robot made from two `SimMotor`s and an optional `SimGyroSensor`.
+///
+/// The robot's pose is calculated from the simulated wheel rotations,
+/// which makes it possible to check the result of `DriveBase` actions against the ground truth.
+///
+/// The pose is in mm and degrees.
+/// The x axis points forwards from the starting position, the y axis points to the right,
+/// and the heading is clockwise, the same as `DriveBase::turn`.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort, SensorPort};
+/// use ev3dev_rs::pupdevices::{GyroSensor, Motor};
+/// use ev3dev_rs::robotics::DriveBase;
+/// use ev3dev_rs::sim::SimDriveBase;
+///
+/// let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 62.4, 130.5)
+///     .with_gyro(SensorPort::In1)
+///     .with_gyro_drift(0.1);
+///
+/// let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+/// let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+/// let gyro = GyroSensor::new(SensorPort::In1)?;
+///
+/// let drive = DriveBase::new(&left, &right, 62.4, 130.5)?.with_gyro(&gyro)?;
+/// drive.use_gyro(true)?;
+///
+/// drive.straight(500).await?;
+///
+/// let (x, y, heading) = robot.pose();
+/// assert!((x - 500.0).abs() < 10.0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct SimDriveBase {
+    world: Arc<World>,
+}
+
+impl SimDriveBase {
+    /// Creates a new simulated robot with motors on the given ports.
+    ///
+    /// Wheel diameter and axle track are in mm.
+    ///
+    /// By default both motors drive the robot forwards when they turn with `Direction::Clockwise`,
+    /// see `with_motor_directions`.
+    pub fn new(
+        left_port: MotorPort,
+        right_port: MotorPort,
+        wheel_diameter: f64,
+        axle_track: f64,
+    ) -> Self {
+        let world = Arc::new(World {
+            state: Mutex::new(WorldState {
+                last_update: Instant::now(),
+                elapsed: 0.0,
+                wheel_diameter,
+                axle_track,
+                left_sign: 1.0,
+                right_sign: 1.0,
+                left_slip: 0.0,
+                right_slip: 0.0,
+                gyro_drift: 0.0,
+                left_angle: 0.0,
+                right_angle: 0.0,
+                x: 0.0,
+                y: 0.0,
+                heading: 0.0,
+            }),
+            left: SimMotor::new(left_port),
+            right: SimMotor::new(right_port),
+            gyro: Mutex::new(None),
+        });
+
+        let simulation: Weak<World> = Arc::downgrade(&world);
+        world.left.device().attach(simulation.clone());
+        world.right.device().attach(simulation);
+
+        Self { world }
+    }
+
+    /// Adds a simulated gyro sensor on the given port that measures the robot's heading.
+    pub fn with_gyro(self, port: SensorPort) -> Self {
+        self.world.advance(Instant::now());
+
+        let gyro = SimGyroSensor::new(port);
+        gyro.device()
+            .attach(Arc::downgrade(&self.world) as Weak<dyn Simulation>);
+
+        let heading = self.lock_state().heading;
+        gyro.device().lock_model().set(heading, 0.0);
+
+        *self
+            .world
+            .gyro
+            .lock()
+            .expect("Tried to use a poisoned lock") = Some(gyro);
+        self
+    }
+
+    /// Sets the motor directions that drive the robot forwards.
+    ///
+    /// These should match the `Direction`s that your code passes to `Motor::new`.
+    pub fn with_motor_directions(self, left: Direction, right: Direction) -> Self {
+        let sign = |direction| match direction {
+            Direction::Clockwise => 1.0,
+            Direction::CounterClockwise => -1.0,
+        };
+
+        {
+            let mut state = self.lock_state();
+            state.left_sign = sign(left);
+            state.right_sign = sign(right);
+        }
+        self
+    }
+
+    /// Sets the fraction of each wheel's rotation that is lost to slipping (0 to 1).
+    pub fn with_wheel_slip(self, left: f64, right: f64) -> Self {
+        {
+            let mut state = self.lock_state();
+            state.left_slip = left.clamp(0.0, 1.0);
+            state.right_slip = right.clamp(0.0, 1.0);
+        }
+        self
+    }
+
+    /// Sets how fast the simulated gyro drifts away from the true heading in degrees per second.
+    pub fn with_gyro_drift(self, drift: f64) -> Self {
+        self.lock_state().gyro_drift = drift;
+        self
+    }
+
+    /// Gets the true pose of the robot as `(x, y, heading)`.
+    pub fn pose(&self) -> (f64, f64, f64) {
+        let state = self.lock_state();
+        (state.x, state.y, state.heading)
+    }
+
+    /// Moves the robot to the given pose without moving the wheels.
+    pub fn set_pose(&self, x: f64, y: f64, heading: f64) {
+        let mut state = self.lock_state();
+        state.x = x;
+        state.y = y;
+        state.heading = heading;
+    }
+
+    /// Gets the simulated left motor.
+    pub fn left_motor(&self) -> &SimMotor {
+        &self.world.left
+    }
+
+    /// Gets the simulated right motor.
+    pub fn right_motor(&self) -> &SimMotor {
+        &self.world.right
+    }
+
+    // advances the world to the current time and locks its state
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, WorldState> {
+        self.world.advance(Instant::now());
+        self.world
+            .state
+            .lock()
+            .expect("Tried to use a poisoned lock")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Ev3Result;
+    use crate::pupdevices::{GyroSensor, Motor};
+    use crate::robotics::DriveBase;
+
+    #[tokio::test(start_paused = true)]
+    async fn turning_moves_the_gyro() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutA, MotorPort::OutD, 56.0, 120.0)
+            .with_gyro(SensorPort::In1);
+
+        let left = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutD, Direction::Clockwise)?;
+        let gyro = GyroSensor::new(SensorPort::In1)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        drive.turn(90).await?;
+
+        let (_, _, heading) = robot.pose();
+        assert!((heading - 90.0).abs() < 2.0, "heading {heading}");
+        assert!((gyro.heading()? - 90).abs() <= 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn straight_moves_forwards() -> Ev3Result<()> {
+        let robot = SimDriveBase::new(MotorPort::OutB, MotorPort::OutC, 56.0, 120.0);
+
+        let left = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+        let right = Motor::new(MotorPort::OutC, Direction::Clockwise)?;
+        let drive = DriveBase::new(&left, &right, 56.0, 120.0)?;
+
+        drive.straight(500).await?;
+
+        let (x, y, heading) = robot.pose();
+        assert!((x - 500.0).abs() < 10.0, "x {x}");
+        assert!(y.abs() < 5.0, "y {y}");
+        assert!(heading.abs() < 1.0, "heading {heading}");
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    attribute::{AttributeName, VirtualDevice},
+    enum_string::AsStr,
+    error::{Ev3Error, Ev3Result},
+    parameters::SensorPort,
+    sensor_driver::{SensorMode, SensorType},
+    sim::{self, Simulation},
+};
+
+pub(crate) struct GyroModel {
+    port: SensorPort,
+    mode: SensorMode,
+    angle: f64,
+    rate: f64,
+}
+
+impl GyroModel {
+    pub(crate) fn set(&mut self, angle: f64, rate: f64) {
+        self.angle = angle;
+        self.rate = rate;
+    }
+
+    fn value(&self, index: usize) -> Ev3Result<String> {
+        let angle = (self.angle.round() as i16).to_string();
+        let rate = (self.rate.round() as i16).to_string();
+
+        let values = match self.mode {
+            SensorMode::GyroAngle => vec![angle],
+            SensorMode::GyroRate | SensorMode::GyroRateUnscaled => vec![rate],
+            SensorMode::GyroAngleAndRate => vec![angle, rate],
+            _ => vec!["0".to_string()],
+        };
+
+        values.get(index).cloned().ok_or(Ev3Error::FileNotFound {
+            path: format!("value{index}").into(),
+        })
+    }
+}
+
+pub(crate) struct SimGyroDevice {
+    model: Mutex<GyroModel>,
+    simulation: Mutex<Option<Weak<dyn Simulation>>>,
+}
+
+impl SimGyroDevice {
+    pub(crate) fn lock_model(&self) -> MutexGuard<'_, GyroModel> {
+        self.model.lock().expect("Tried to use a poisoned lock")
+    }
+
+    pub(crate) fn attach(&self, simulation: Weak<dyn Simulation>) {
+        *self
+            .simulation
+            .lock()
+            .expect("Tried to use a poisoned lock") = Some(simulation);
+    }
+
+    fn with_model<T>(&self, f: impl FnOnce(&mut GyroModel) -> T) -> T {
+        sim::advance(&self.simulation, Instant::now());
+        f(&mut self.lock_model())
+    }
+}
+
+impl VirtualDevice for SimGyroDevice {
+    fn read(&self, name: AttributeName) -> Ev3Result<String> {
+        self.with_model(|model| match name {
+            AttributeName::Address => Ok(model.port.as_str().into()),
+            AttributeName::DriverName => Ok(SensorType::Gyro.as_str().into()),
+            AttributeName::Mode => Ok(model.mode.as_str().into()),
+            AttributeName::Modes => {
+                Ok("GYRO-ANG GYRO-RATE GYRO-FAS GYRO-G&A GYRO-CAL TILT-RATE TILT-ANG".into())
+            }
+            AttributeName::Value0 => model.value(0),
+            AttributeName::Value1 => model.value(1),
+            _ => Err(Ev3Error::FileNotFound {
+                path: name.to_string().into(),
+            }),
+        })
+    }
+
+    fn write(&self, name: AttributeName, value: &str) -> Ev3Result<()> {
+        self.with_model(|model| match name {
+            AttributeName::Mode => {
+                model.mode = SensorMode::from_str(value)?;
+                Ok(())
+            }
+            _ => Err(Ev3Error::PermissionDenied {
+                required_permission: name.filemode(),
+            }),
+        })
+    }
+}
+
+/// A simulated EV3 gyro sensor.
+///
+/// While a `SimGyroSensor` exists, `GyroSensor::new` called on the same thread with the same port
+/// will connect to the simulation instead of looking for a real sensor.
+///
+/// The readings can be set by hand, or driven by a `SimDriveBase`.
+pub struct SimGyroSensor {
+    device: Arc<SimGyroDevice>,
+}
+
+impl SimGyroSensor {
+    /// Creates a new simulated gyro sensor on the given port.
+    ///
+    /// This replaces any other simulated sensor on the same port.
+    pub fn new(port: SensorPort) -> Self {
+        let device = Arc::new(SimGyroDevice {
+            model: Mutex::new(GyroModel {
+                port,
+                mode: SensorMode::GyroAngle,
+                angle: 0.0,
+                rate: 0.0,
+            }),
+            simulation: Mutex::new(None),
+        });
+        sim::register_sensor(port, device.clone());
+        Self { device }
+    }
+
+    pub(crate) fn device(&self) -> &Arc<SimGyroDevice> {
+        &self.device
+    }
+
+    /// Gets the port of the simulated sensor.
+    pub fn port(&self) -> SensorPort {
+        self.device.lock_model().port
+    }
+
+    /// Sets the heading reported by the sensor in degrees.
+    pub fn set_angle(&self, angle: f64) {
+        self.device.with_model(|model| model.angle = angle);
+    }
+
+    /// Sets the angular velocity reported by the sensor in degrees per second.
+    pub fn set_rate(&self, rate: f64) {
+        self.device.with_model(|model| model.rate = rate);
+    }
+
+    /// Gets the heading that the sensor is currently reporting in degrees, before rounding.
+    pub fn angle(&self) -> f64 {
+        self.device.with_model(|model| model.angle)
+    }
+
+    /// Gets the angular velocity that the sensor is currently reporting in degrees per second, before rounding.
+    pub fn rate(&self) -> f64 {
+        self.device.with_model(|model| model.rate)
+    }
+}
+
+impl Drop for SimGyroSensor {
+    fn drop(&mut self) {
+        sim::unregister_sensor(&(self.device.clone() as Arc<dyn VirtualDevice>));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pupdevices::GyroSensor;
+
+    #[test]
+    fn readings_are_rounded() -> Ev3Result<()> {
+        let sim = SimGyroSensor::new(SensorPort::In2);
+        let gyro = GyroSensor::new(SensorPort::In2)?;
+
+        sim.set_angle(44.6);
+        sim.set_rate(-10.2);
+        assert_eq!(gyro.heading_and_velocity()?, (45, -10));
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    cell::RefCell,
+    sync::{Arc, Mutex, Weak},
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    attribute::VirtualDevice,
+    parameters::{MotorPort, SensorPort},
+};
+
+mod drive_base;
+mod gyro;
+mod motor;
+#[cfg(test)]
+mod test_sensor;
+
+pub use drive_base::SimDriveBase;
+pub use gyro::SimGyroSensor;
+pub use motor::SimMotor;
+#[cfg(test)]
+pub(crate) use test_sensor::TestSensor;
+
+use motor::SimMotorDevice;
+
+// the size of a single physics step (seconds)
+const STEP: f64 = 0.001;
+
+thread_local! {
+    static MOTORS: RefCell<Vec<Arc<SimMotorDevice>>> = const { RefCell::new(Vec::new()) };
+    static SENSORS: RefCell<Vec<(SensorPort, Arc<dyn VirtualDevice>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Something that moves multiple simulated devices forward in time together.
+pub(crate) trait Simulation: Send + Sync {
+    fn advance(&self, now: Instant);
+}
+
+// advances the simulation a device belongs to, if it belongs to one
+fn advance(simulation: &Mutex<Option<Weak<dyn Simulation>>>, now: Instant) {
+    let simulation = simulation
+        .lock()
+        .expect("Tried to use a poisoned lock")
+        .as_ref()
+        .and_then(Weak::upgrade);
+
+    if let Some(simulation) = simulation {
+        simulation.advance(now);
+    }
+}
+
+pub(crate) fn find_motor(port: MotorPort) -> Option<Arc<dyn VirtualDevice>> {
+    MOTORS.with_borrow(|motors| {
+        motors
+            .iter()
+            .find(|motor| motor.port() == port)
+            .map(|motor| motor.clone() as Arc<dyn VirtualDevice>)
+    })
+}
+
+pub(crate) fn find_sensor(port: SensorPort) -> Option<Arc<dyn VirtualDevice>> {
+    SENSORS.with_borrow(|sensors| {
+        sensors
+            .iter()
+            .find(|(sensor_port, _)| *sensor_port == port)
+            .map(|(_, sensor)| sensor.clone())
+    })
+}
+
+fn register_motor(device: Arc<SimMotorDevice>) {
+    MOTORS.with_borrow_mut(|motors| {
+        let port = device.port();
+        motors.retain(|motor| motor.port() != port);
+        motors.push(device);
+    });
+}
+
+fn unregister_motor(device: &Arc<SimMotorDevice>) {
+    // try_with because this can run while the thread is shutting down
+    _ = MOTORS.try_with(|motors| {
+        motors
+            .borrow_mut()
+            .retain(|motor| !Arc::ptr_eq(motor, device))
+    });
+}
+
+fn register_sensor(port: SensorPort, device: Arc<dyn VirtualDevice>) {
+    SENSORS.with_borrow_mut(|sensors| {
+        sensors.retain(|(sensor_port, _)| *sensor_port != port);
+        sensors.push((port, device));
+    });
+}
+
+fn unregister_sensor(device: &Arc<dyn VirtualDevice>) {
+    // try_with because this can run while the thread is shutting down
+    _ = SENSORS.try_with(|sensors| {
+        sensors
+            .borrow_mut()
+            .retain(|(_, sensor)| !Arc::ptr_eq(sensor, device))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simulated_devices_are_found_while_they_exist() {
+        let motor = SimMotor::new(MotorPort::OutC);
+        let gyro = SimGyroSensor::new(SensorPort::In3);
+        assert!(find_motor(MotorPort::OutC).is_some());
+        assert!(find_sensor(SensorPort::In3).is_some());
+        assert!(find_motor(MotorPort::OutD).is_none());
+
+        drop(motor);
+        drop(gyro);
+        assert!(find_motor(MotorPort::OutC).is_none());
+        assert!(find_sensor(SensorPort::In3).is_none());
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    attribute::{AttributeName, VirtualDevice},
+    enum_string::AsStr,
+    error::{Ev3Error, Ev3Result},
+    parameters::{MotorPort, Stop},
+    sim::{self, STEP, Simulation},
+};
+
+const COUNT_PER_ROT: u32 = 360;
+// degrees per second, the same as a real EV3 large motor
+const MAX_SPEED: f64 = 1050.0;
+// how fast the motor reacts to a change in effort (seconds)
+const DRIVE_TIME_CONSTANT: f64 = 0.05;
+// how fast a coasting motor slows down by itself (seconds)
+const COAST_TIME_CONSTANT: f64 = 0.25;
+// deceleration used for position commands when no ramp down setpoint is set (degrees per second squared)
+const DEFAULT_DECELERATION: f64 = 4000.0;
+// duty cycle per degree of error while holding
+const HOLD_GAIN: f64 = 0.05;
+// how long the motor has to be unable to move before it reports a stall (seconds)
+const STALL_TIME: f64 = 0.1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum RunMode {
+    Stopped,
+    Forever,
+    ToPosition(f64),
+    Timed(f64),
+    Direct,
+}
+
+pub(crate) struct Model {
+    port: MotorPort,
+    load: f64,
+    last_update: Instant,
+
+    // attributes that are written by the user
+    inversed: bool,
+    speed_sp: i32,
+    position_sp: i32,
+    time_sp: u32,
+    ramp_up_sp: u32,
+    ramp_down_sp: u32,
+    duty_cycle_sp: i32,
+    stop_action: Stop,
+    other: HashMap<AttributeName, String>,
+
+    // physical state, always with normal polarity
+    mode: RunMode,
+    hold_target: Option<f64>,
+    position: f64,
+    speed: f64,
+    speed_ref: f64,
+    duty: f64,
+    ramping: bool,
+    stall_time: f64,
+}
+
+impl Model {
+    fn new(port: MotorPort) -> Self {
+        let mut model = Self {
+            port,
+            load: 0.0,
+            last_update: Instant::now(),
+            inversed: false,
+            speed_sp: 0,
+            position_sp: 0,
+            time_sp: 0,
+            ramp_up_sp: 0,
+            ramp_down_sp: 0,
+            duty_cycle_sp: 0,
+            stop_action: Stop::Coast,
+            other: HashMap::new(),
+            mode: RunMode::Stopped,
+            hold_target: None,
+            position: 0.0,
+            speed: 0.0,
+            speed_ref: 0.0,
+            duty: 0.0,
+            ramping: false,
+            stall_time: 0.0,
+        };
+        model.reset();
+        model
+    }
+
+    fn reset(&mut self) {
+        self.inversed = false;
+        self.speed_sp = 0;
+        self.position_sp = 0;
+        self.time_sp = 0;
+        self.ramp_up_sp = 0;
+        self.ramp_down_sp = 0;
+        self.duty_cycle_sp = 0;
+        self.stop_action = Stop::Coast;
+        self.mode = RunMode::Stopped;
+        self.hold_target = None;
+        self.position = 0.0;
+        self.speed_ref = 0.0;
+        self.duty = 0.0;
+        self.stall_time = 0.0;
+
+        self.other.clear();
+        for (name, value) in [
+            (AttributeName::HoldPidKp, "4000"),
+            (AttributeName::HoldPidKi, "0"),
+            (AttributeName::HoldPidKd, "0"),
+            (AttributeName::SpeedPidKp, "1000"),
+            (AttributeName::SpeedPidKi, "60"),
+            (AttributeName::SpeedPidKd, "0"),
+        ] {
+            self.other.insert(name, value.into());
+        }
+    }
+
+    // the physical position of the shaft in degrees
+    pub(crate) fn position(&self) -> f64 {
+        self.position
+    }
+
+    fn sign(&self) -> f64 {
+        if self.inversed { -1.0 } else { 1.0 }
+    }
+
+    pub(crate) fn advance_to(&mut self, now: Instant) {
+        let mut remaining = now
+            .saturating_duration_since(self.last_update)
+            .as_secs_f64();
+        self.last_update = now;
+
+        while remaining > 0.0 {
+            let dt = remaining.min(STEP);
+            self.step(dt);
+            remaining -= dt;
+        }
+    }
+
+    pub(crate) fn step(&mut self, dt: f64) {
+        match self.mode {
+            RunMode::Stopped => {
+                self.ramping = false;
+                self.speed_ref = 0.0;
+                self.duty = match self.hold_target {
+                    Some(target) => (HOLD_GAIN * (target - self.position)).clamp(-1.0, 1.0),
+                    None => 0.0,
+                };
+            }
+            RunMode::Forever => self.regulate(self.sign() * f64::from(self.speed_sp), dt),
+            RunMode::Timed(remaining) => {
+                if remaining <= 0.0 {
+                    self.stop();
+                } else {
+                    self.mode = RunMode::Timed(remaining - dt);
+                    self.regulate(self.sign() * f64::from(self.speed_sp), dt);
+                }
+            }
+            RunMode::ToPosition(target) => {
+                let error = target - self.position;
+                let direction = self.speed_ref.signum();
+
+                if error.abs() < 0.5 || (self.speed_ref != 0.0 && error * direction < 0.0) {
+                    self.stop();
+                } else {
+                    let deceleration = if self.ramp_down_sp > 0 {
+                        MAX_SPEED * 1000.0 / f64::from(self.ramp_down_sp)
+                    } else {
+                        DEFAULT_DECELERATION
+                    };
+
+                    // slow down in time to stop on the target
+                    let speed = f64::from(self.speed_sp.unsigned_abs())
+                        .min((2.0 * deceleration * error.abs()).sqrt());
+
+                    self.regulate(speed * error.signum(), dt);
+                }
+            }
+            RunMode::Direct => {
+                self.ramping = false;
+                self.duty = (self.sign() * f64::from(self.duty_cycle_sp) / 100.0).clamp(-1.0, 1.0);
+            }
+        }
+
+        // the load acts like a torque that the motor has to overcome before it can move
+        let effective_duty = if self.duty.abs() > self.load {
+            self.duty - self.load * self.duty.signum()
+        } else {
+            0.0
+        };
+
+        let time_constant = if self.mode == RunMode::Stopped
+            && self.hold_target.is_none()
+            && self.stop_action == Stop::Coast
+        {
+            COAST_TIME_CONSTANT
+        } else {
+            DRIVE_TIME_CONSTANT
+        };
+
+        self.speed += (effective_duty * MAX_SPEED - self.speed) * (dt / time_constant).min(1.0);
+        self.position += self.speed * dt;
+
+        // the motor is stalled if it should be moving but isn't
+        let unloaded_speed = self.duty * MAX_SPEED;
+        if self.mode != RunMode::Stopped
+            && unloaded_speed.abs() > 20.0
+            && self.speed.abs() < unloaded_speed.abs() * 0.2
+        {
+            self.stall_time += dt;
+        } else {
+            self.stall_time = 0.0;
+        }
+    }
+
+    // moves the speed reference towards the target speed, respecting the ramp setpoints,
+    // and picks a duty cycle that makes up for the load like the real speed regulator would
+    fn regulate(&mut self, target_speed: f64, dt: f64) {
+        let speeding_up = target_speed.abs() > self.speed_ref.abs()
+            || target_speed.signum() != self.speed_ref.signum();
+
+        let ramp = if speeding_up {
+            self.ramp_up_sp
+        } else {
+            self.ramp_down_sp
+        };
+
+        if ramp == 0 {
+            self.speed_ref = target_speed;
+        } else {
+            let max_change = MAX_SPEED * dt * 1000.0 / f64::from(ramp);
+            self.speed_ref += (target_speed - self.speed_ref).clamp(-max_change, max_change);
+        }
+
+        self.ramping = (target_speed - self.speed_ref).abs() > f64::EPSILON;
+        self.duty =
+            (self.speed_ref / MAX_SPEED + self.load * self.speed_ref.signum()).clamp(-1.0, 1.0);
+    }
+
+    fn stop(&mut self) {
+        self.hold_target = match self.stop_action {
+            Stop::Hold => Some(match self.mode {
+                RunMode::ToPosition(target) => target,
+                _ => self.position,
+            }),
+            _ => None,
+        };
+        self.mode = RunMode::Stopped;
+    }
+
+    fn command(&mut self, command: &str) -> Ev3Result<()> {
+        let sign = self.sign();
+
+        self.hold_target = None;
+        self.mode = match command {
+            "run-forever" => RunMode::Forever,
+            "run-to-abs-pos" => RunMode::ToPosition(sign * f64::from(self.position_sp)),
+            "run-to-rel-pos" => {
+                RunMode::ToPosition(self.position + sign * f64::from(self.position_sp))
+            }
+            "run-timed" => RunMode::Timed(f64::from(self.time_sp) / 1000.0),
+            "run-direct" => RunMode::Direct,
+            "stop" => {
+                self.stop();
+                return Ok(());
+            }
+            "reset" => {
+                self.reset();
+                return Ok(());
+            }
+            _ => {
+                return Err(Ev3Error::InvalidValue {
+                    func: "SimMotor::command".into(),
+                    value: command.into(),
+                });
+            }
+        };
+
+        Ok(())
+    }
+
+    fn state(&self) -> String {
+        let mut flags = Vec::new();
+
+        if self.mode != RunMode::Stopped {
+            flags.push("running");
+            if self.ramping {
+                flags.push("ramping");
+            }
+        } else if self.hold_target.is_some() {
+            flags.push("holding");
+        }
+
+        if self.stall_time >= STALL_TIME {
+            flags.push("stalled");
+        }
+
+        flags.join(" ")
+    }
+}
+
+pub(crate) struct SimMotorDevice {
+    model: Mutex<Model>,
+    simulation: Mutex<Option<Weak<dyn Simulation>>>,
+}
+
+impl SimMotorDevice {
+    pub(crate) fn port(&self) -> MotorPort {
+        self.model
+            .lock()
+            .expect("Tried to use a poisoned lock")
+            .port
+    }
+
+    pub(crate) fn lock_model(&self) -> MutexGuard<'_, Model> {
+        self.model.lock().expect("Tried to use a poisoned lock")
+    }
+
+    pub(crate) fn attach(&self, simulation: Weak<dyn Simulation>) {
+        *self
+            .simulation
+            .lock()
+            .expect("Tried to use a poisoned lock") = Some(simulation);
+    }
+
+    fn with_model<T>(&self, f: impl FnOnce(&mut Model) -> T) -> T {
+        let now = Instant::now();
+        sim::advance(&self.simulation, now);
+
+        let mut model = self.lock_model();
+        model.advance_to(now);
+        f(&mut model)
+    }
+}
+
+impl VirtualDevice for SimMotorDevice {
+    fn read(&self, name: AttributeName) -> Ev3Result<String> {
+        self.with_model(|model| {
+            let sign = model.sign();
+            Ok(match name {
+                AttributeName::Address => model.port.as_str().into(),
+                AttributeName::DriverName => "lego-ev3-l-motor".into(),
+                AttributeName::Commands => {
+                    "run-forever run-to-abs-pos run-to-rel-pos run-timed run-direct stop reset"
+                        .into()
+                }
+                AttributeName::CountPerRotation => COUNT_PER_ROT.to_string(),
+                AttributeName::MaxSpeed => (MAX_SPEED as i32).to_string(),
+                AttributeName::DutyCycle => {
+                    ((sign * model.duty * 100.0).round() as i32).to_string()
+                }
+                AttributeName::DutyCycleSetpoint => model.duty_cycle_sp.to_string(),
+                AttributeName::Polarity => {
+                    if model.inversed { "inversed" } else { "normal" }.into()
+                }
+                AttributeName::Position => ((sign * model.position).round() as i32).to_string(),
+                AttributeName::PositionSetpoint => model.position_sp.to_string(),
+                AttributeName::Speed => ((sign * model.speed).round() as i32).to_string(),
+                AttributeName::SpeedSetpoint => model.speed_sp.to_string(),
+                AttributeName::RampUpSetpoint => model.ramp_up_sp.to_string(),
+                AttributeName::RampDownSetpoint => model.ramp_down_sp.to_string(),
+                AttributeName::State => model.state(),
+                AttributeName::StopAction => model.stop_action.as_str().into(),
+                AttributeName::StopActions => "coast brake hold".into(),
+                AttributeName::TimeSetpoint => model.time_sp.to_string(),
+                _ => match model.other.get(&name) {
+                    Some(value) => value.clone(),
+                    None => {
+                        return Err(Ev3Error::FileNotFound {
+                            path: name.to_string().into(),
+                        });
+                    }
+                },
+            })
+        })
+    }
+
+    fn write(&self, name: AttributeName, value: &str) -> Ev3Result<()> {
+        self.with_model(|model| {
+            match name {
+                AttributeName::Command => model.command(value)?,
+                AttributeName::DutyCycleSetpoint => {
+                    model.duty_cycle_sp = value.parse::<i32>()?.clamp(-100, 100)
+                }
+                AttributeName::Polarity => {
+                    model.inversed = match value {
+                        "normal" => false,
+                        "inversed" => true,
+                        _ => {
+                            return Err(Ev3Error::InvalidValue {
+                                func: "SimMotor::polarity".into(),
+                                value: value.into(),
+                            });
+                        }
+                    }
+                }
+                AttributeName::Position => {
+                    let position = model.sign() * f64::from(value.parse::<i32>()?);
+                    if let Some(ref mut target) = model.hold_target {
+                        *target += position - model.position;
+                    }
+                    model.position = position;
+                }
+                AttributeName::PositionSetpoint => model.position_sp = value.parse()?,
+                AttributeName::SpeedSetpoint => model.speed_sp = value.parse()?,
+                AttributeName::RampUpSetpoint => model.ramp_up_sp = value.parse()?,
+                AttributeName::RampDownSetpoint => model.ramp_down_sp = value.parse()?,
+                AttributeName::StopAction => model.stop_action = Stop::from_str(value)?,
+                AttributeName::TimeSetpoint => model.time_sp = value.parse()?,
+                _ => _ = model.other.insert(name, value.into()),
+            }
+            Ok(())
+        })
+    }
+}
+
+/// A simulated tacho motor.
+///
+/// While a `SimMotor` exists, `Motor::new` called on the same thread with the same port
+/// will connect to the simulation instead of looking for a real motor.
+///
+/// The simulation reacts to the same commands as a real motor
+/// (run-forever, run-to-abs-pos, run-to-rel-pos, run-timed, run-direct, stop and reset),
+/// respects the speed, position, time, duty cycle and ramp setpoints,
+/// and reports the running, ramping, holding and stalled states.
+///
+/// Time is measured with `tokio::time::Instant`,
+/// so the simulation also works with a paused tokio clock.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::{Direction, MotorPort};
+/// use ev3dev_rs::pupdevices::Motor;
+/// use ev3dev_rs::sim::SimMotor;
+///
+/// let sim = SimMotor::new(MotorPort::OutA);
+/// let motor = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+///
+/// motor.run_angle(500, 180).await?;
+/// assert!((sim.angle() - 180.0).abs() < 2.0);
+///
+/// // the motor can't overcome a load of 100%, so this will stop on a stall
+/// sim.set_load(1.0);
+/// motor.run_until_stalled(50).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SimMotor {
+    device: Arc<SimMotorDevice>,
+}
+
+impl SimMotor {
+    /// Creates a new simulated motor on the given port.
+    ///
+    /// This replaces any other simulated motor on the same port.
+    pub fn new(port: MotorPort) -> Self {
+        let device = Arc::new(SimMotorDevice {
+            model: Mutex::new(Model::new(port)),
+            simulation: Mutex::new(None),
+        });
+        sim::register_motor(device.clone());
+        Self { device }
+    }
+
+    pub(crate) fn device(&self) -> &Arc<SimMotorDevice> {
+        &self.device
+    }
+
+    /// Gets the port of the simulated motor.
+    pub fn port(&self) -> MotorPort {
+        self.device.port()
+    }
+
+    /// Sets the load on the motor as a fraction of its stall torque (0 to 1).
+    ///
+    /// A load of 1 or more keeps the motor from moving at all, which will cause it to stall.
+    pub fn set_load(&self, load: f64) {
+        self.device.with_model(|model| model.load = load.max(0.0));
+    }
+
+    /// Gets the angle of the motor shaft in degrees.
+    ///
+    /// Unlike `Motor::angle`, this always uses normal polarity and is not rounded.
+    pub fn angle(&self) -> f64 {
+        self.device.with_model(|model| model.position)
+    }
+
+    /// Gets the speed of the motor shaft in degrees per second.
+    ///
+    /// Unlike the speed reported to `Motor`, this always uses normal polarity.
+    pub fn speed(&self) -> f64 {
+        self.device.with_model(|model| model.speed)
+    }
+
+    /// Gets the duty cycle that is currently applied to the motor (-1 to 1).
+    pub fn duty_cycle(&self) -> f64 {
+        self.device.with_model(|model| model.duty)
+    }
+
+    /// Returns `true` if the motor is currently running a command.
+    pub fn is_running(&self) -> bool {
+        self.device
+            .with_model(|model| model.mode != RunMode::Stopped)
+    }
+
+    /// Returns `true` if the motor is currently reporting a stall.
+    pub fn is_stalled(&self) -> bool {
+        self.device
+            .with_model(|model| model.stall_time >= STALL_TIME)
+    }
+}
+
+impl Drop for SimMotor {
+    fn drop(&mut self) {
+        sim::unregister_motor(&self.device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{sleep, timeout};
+
+    use super::*;
+    use crate::parameters::Direction;
+    use crate::pupdevices::Motor;
+
+    #[tokio::test(start_paused = true)]
+    async fn run_target_reaches_target() -> Ev3Result<()> {
+        let sim = SimMotor::new(MotorPort::OutA);
+        let motor = Motor::new(MotorPort::OutA, Direction::Clockwise)?;
+
+        motor.run_target(500, 270).await?;
+        assert!((sim.angle() - 270.0).abs() < 2.0, "angle {}", sim.angle());
+        assert!((motor.angle()? - 270).abs() <= 2);
+
+        motor.run_target(800, -90).await?;
+        assert!((sim.angle() + 90.0).abs() < 2.0, "angle {}", sim.angle());
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_load_stalls() -> Ev3Result<()> {
+        let sim = SimMotor::new(MotorPort::OutB);
+        let motor = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+
+        sim.set_load(1.0);
+        motor.run(500)?;
+        sleep(Duration::from_secs(1)).await;
+
+        assert!(sim.is_stalled());
+        assert!(sim.angle().abs() < 1.0, "angle {}", sim.angle());
+
+        // run_until_stalled has to return instead of waiting forever
+        timeout(Duration::from_secs(5), motor.run_until_stalled(50))
+            .await
+            .expect("the motor never stalled")?;
+        Ok(())
+    }
+}
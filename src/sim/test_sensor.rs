@@ -0,0 +1,85 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    attribute::{AttributeName, VirtualDevice},
+    enum_string::AsStr,
+    error::{Ev3Error, Ev3Result},
+    parameters::SensorPort,
+    sensor_driver::{SensorMode, SensorType},
+    sim,
+};
+
+struct TestSensorDevice {
+    port: SensorPort,
+    sensor_type: SensorType,
+    mode: Mutex<SensorMode>,
+    value: Box<dyn Fn() -> i32 + Send + Sync>,
+}
+
+impl VirtualDevice for TestSensorDevice {
+    fn read(&self, name: AttributeName) -> Ev3Result<String> {
+        match name {
+            AttributeName::Address => Ok(self.port.as_str().into()),
+            AttributeName::DriverName => Ok(self.sensor_type.as_str().into()),
+            AttributeName::Mode => Ok(self
+                .mode
+                .lock()
+                .expect("Tried to use a poisoned lock")
+                .as_str()
+                .into()),
+            AttributeName::Value0 => Ok((self.value)().to_string()),
+            _ => Err(Ev3Error::FileNotFound {
+                path: name.to_string().into(),
+            }),
+        }
+    }
+
+    fn write(&self, name: AttributeName, value: &str) -> Ev3Result<()> {
+        match name {
+            AttributeName::Mode => {
+                *self.mode.lock().expect("Tried to use a poisoned lock") =
+                    SensorMode::from_str(value)?;
+                Ok(())
+            }
+            _ => Err(Ev3Error::PermissionDenied {
+                required_permission: name.filemode(),
+            }),
+        }
+    }
+}
+
+/// A simulated sensor for tests, whose first value is calculated by a closure on every read,
+/// for example from the pose of a `SimDriveBase`.
+pub(crate) struct TestSensor {
+    device: Arc<TestSensorDevice>,
+}
+
+impl TestSensor {
+    pub(crate) fn new<F>(
+        port: SensorPort,
+        sensor_type: SensorType,
+        mode: SensorMode,
+        value: F,
+    ) -> Self
+    where
+        F: Fn() -> i32 + Send + Sync + 'static,
+    {
+        let device = Arc::new(TestSensorDevice {
+            port,
+            sensor_type,
+            mode: Mutex::new(mode),
+            value: Box::new(value),
+        });
+        sim::register_sensor(port, device.clone());
+        Self { device }
+    }
+}
+
+impl Drop for TestSensor {
+    fn drop(&mut self) {
+        sim::unregister_sensor(&(self.device.clone() as Arc<dyn VirtualDevice>));
+    }
+}
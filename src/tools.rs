@@ -1,11 +1,90 @@
-use std::time::Duration;
-use tokio::time::sleep;
+use std::{env, path::PathBuf, sync::RwLock, time::Duration};
+use tokio::time::{interval, sleep};
+
+use crate::error::Ev3Result;
+
+static DEVICE_ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
 
 /// A pybricks-like async wait function.
 pub async fn wait(duration: Duration) {
     sleep(duration).await;
 }
 
+/// Waits until the condition returns true, checking it every 5 ms.
+///
+/// Any error from the condition is returned immediately.
+///
+/// This turns a closure into a future, for use with `select!` or `DriveBase::straight_until`.
+///
+/// # Examples
+/// ``` no_run
+/// # async fn example() -> ev3dev_rs::Ev3Result<()> {
+/// use ev3dev_rs::parameters::SensorPort;
+/// use ev3dev_rs::pupdevices::ColorSensor;
+/// use ev3dev_rs::tools::wait_until;
+///
+/// let color_sensor = ColorSensor::new(SensorPort::In1)?;
+///
+/// // wait for a black line
+/// wait_until(|| Ok(color_sensor.reflection()? < 15)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn wait_until<F>(mut condition: F) -> Ev3Result<()>
+where
+    F: FnMut() -> Ev3Result<bool>,
+{
+    let mut timer = interval(Duration::from_millis(5));
+
+    while !condition()? {
+        timer.tick().await;
+    }
+
+    Ok(())
+}
+
+/// Sets the root directory that all devices are looked up in for this process.
+///
+/// By default this is `/`, so motors are found in `/sys/class/tacho-motor`
+/// and sensors are found in `/sys/class/lego-sensor`.
+/// The default can also be changed with the `EV3DEV_ROOT` environment variable.
+///
+/// Pointing this at a directory containing a fake device tree
+/// makes it possible to run your code on a computer without an EV3.
+///
+/// Devices that were already created are not affected.
+///
+/// # Examples
+/// ``` no_run
+/// use ev3dev_rs::tools::set_device_root;
+///
+/// // motors are now found in /tmp/fake-ev3/sys/class/tacho-motor
+/// set_device_root("/tmp/fake-ev3");
+/// ```
+pub fn set_device_root<P>(root: P)
+where
+    P: Into<PathBuf>,
+{
+    *DEVICE_ROOT.write().expect("Tried to use a poisoned lock") = Some(root.into());
+}
+
+/// Gets the root directory that devices are currently looked up in.
+///
+/// See `set_device_root`.
+pub fn device_root() -> PathBuf {
+    if let Some(root) = DEVICE_ROOT
+        .read()
+        .expect("Tried to use a poisoned lock")
+        .as_ref()
+    {
+        return root.clone();
+    }
+
+    env::var_os("EV3DEV_ROOT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
 /// A non-racing multitasker.
 ///
 /// # Examples
@@ -33,3 +112,64 @@ macro_rules! select {
         ev3dev_rs::Race::race(($($fut),+)).await
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use scopeguard::defer;
+
+    use super::*;
+    use crate::parameters::{Direction, MotorPort, Stop};
+    use crate::pupdevices::Motor;
+
+    #[test]
+    fn set_device_root_finds_fake_motor() -> Ev3Result<()> {
+        let root = env::temp_dir().join(format!("ev3dev-rs-device-root-{}", std::process::id()));
+        let motor_dir = root.join("sys/class/tacho-motor/motor0");
+        fs::create_dir_all(&motor_dir).expect("failed to create the fake device tree");
+
+        for (name, value) in [
+            ("address", "ev3-ports:outB"),
+            ("command", ""),
+            ("polarity", "normal"),
+            ("count_per_rot", "360"),
+            ("stop_action", "coast"),
+            ("position", "42"),
+        ] {
+            fs::write(motor_dir.join(name), value).expect("failed to create a fake attribute");
+        }
+
+        // the device root is global, so the other tests get the old one back however this ends
+        let previous = DEVICE_ROOT
+            .read()
+            .expect("Tried to use a poisoned lock")
+            .clone();
+        defer! {
+            *DEVICE_ROOT.write().expect("Tried to use a poisoned lock") = previous;
+            _ = fs::remove_dir_all(&root);
+        }
+
+        set_device_root(&root);
+        assert_eq!(device_root(), root);
+
+        let motor = Motor::new(MotorPort::OutB, Direction::Clockwise)?;
+        assert_eq!(motor.angle()?, 42);
+        assert_eq!(
+            fs::read_to_string(motor_dir.join("command"))
+                .ok()
+                .as_deref(),
+            Some("reset")
+        );
+
+        // a shorter value replaces the whole previous value in a regular file
+        motor.set_stop_action(Stop::Hold)?;
+        assert_eq!(
+            fs::read_to_string(motor_dir.join("stop_action"))
+                .ok()
+                .as_deref(),
+            Some("hold")
+        );
+        Ok(())
+    }
+}